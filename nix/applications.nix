@@ -20,7 +20,10 @@ in
     pname = cargoToml.package.name;
     version = cargoToml.package.version;
 
-    src = ../plugins/applications/.;
+    # the plugin interface crate lives next to the plugin
+    src = ../.;
+    cargoRoot = "plugins/applications";
+    buildAndTestSubdir = "plugins/applications";
 
     buildInputs = [
       pkg-config
@@ -45,9 +48,6 @@ in
 
     cargoLock = {
       inherit lockFile;
-      outputHashes = {
-        "oxiced-0.5.1" = "sha256-pjRHbeuQrbN66AAdpZyhOZ5+xr/XssYgk/DLRR0vCk0=";
-      };
     };
 
     copyLibs = true;
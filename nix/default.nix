@@ -43,7 +43,10 @@ in
     pname = cargoToml.package.name;
     inherit (cargoToml.package) version;
 
-    src = ../oxirun/.;
+    # the plugin interface crate lives next to the binary
+    src = ../.;
+    cargoRoot = "oxirun";
+    buildAndTestSubdir = "oxirun";
 
     buildInputs = [
       pkg-config
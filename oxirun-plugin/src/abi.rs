@@ -0,0 +1,178 @@
+//! The types shared between OxiRun and its plugins.
+//!
+//! Every type crossing the library boundary is `#[repr(C)]`, which means host and plugin do not
+//! need to be built with the same compiler or dependency versions.
+//! Increase [`ABI_VERSION`] whenever one of these types changes.
+
+use std::ffi::c_void;
+
+/// The ABI version this crate describes, plugins reporting a different version are rejected.
+pub const ABI_VERSION: u32 = 9;
+
+/// Symbol of `extern "C" fn() -> u32`, returns the [`ABI_VERSION`] the plugin was built with.
+pub const ABI_VERSION_SYMBOL: &[u8] = b"oxirun_abi_version";
+/// Symbol of `extern "C" fn() -> &'static PluginVTable`.
+pub const VTABLE_SYMBOL: &[u8] = b"oxirun_plugin_vtable";
+
+/// Opaque state of a plugin, only the plugin itself knows what this points to.
+pub type PluginState = *mut c_void;
+
+/// A borrowed UTF-8 string.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FfiStr {
+    ptr: *const u8,
+    len: usize,
+}
+
+impl FfiStr {
+    pub fn new(value: &str) -> Self {
+        Self {
+            ptr: value.as_ptr(),
+            len: value.len(),
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self::new("")
+    }
+
+    /// # Safety
+    /// The string must still be alive and contain valid UTF-8.
+    pub unsafe fn as_str<'a>(&self) -> &'a str {
+        if self.len == 0 {
+            return "";
+        }
+        unsafe { std::str::from_utf8_unchecked(std::slice::from_raw_parts(self.ptr, self.len)) }
+    }
+}
+
+/// A borrowed slice.
+#[repr(C)]
+#[derive(Debug)]
+pub struct FfiSlice<T> {
+    ptr: *const T,
+    len: usize,
+}
+
+impl<T> Clone for FfiSlice<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for FfiSlice<T> {}
+
+impl<T> FfiSlice<T> {
+    pub fn new(value: &[T]) -> Self {
+        Self {
+            ptr: value.as_ptr(),
+            len: value.len(),
+        }
+    }
+
+    /// # Safety
+    /// The slice must still be alive.
+    pub unsafe fn as_slice<'a>(&self) -> &'a [T] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+/// A single entry shown by the host, empty strings are treated as missing values.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FfiEntry {
+    /// Stable id of the entry, passed back to the plugin on launch
+    pub id: u64,
+    /// Score used to rank this entry against the entries of all plugins
+    pub score: i64,
+    pub title: FfiStr,
+    pub subtitle: FfiStr,
+    /// Path to a png or svg file
+    pub icon: FfiStr,
+}
+
+/// An action offered for a single entry, empty strings are treated as missing values.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FfiEntryAction {
+    /// Id of the action, passed back to the plugin together with the id of the entry
+    pub id: u64,
+    pub title: FfiStr,
+    /// Path to a png or svg file
+    pub icon: FfiStr,
+}
+
+/// Passed to the plugin on creation, allows the plugin to wake up the host.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct HostCallbacks {
+    /// Identifies the plugin, this is not a pointer to memory and stays valid forever
+    pub context: *mut c_void,
+    /// Tells the host that the plugin has pending work, the host will call
+    /// [`PluginVTable::update`] on its UI thread afterwards.
+    pub notify: extern "C" fn(context: *mut c_void),
+    /// Whether OxiRun runs as daemon, the plugin then stays loaded until OxiRun is stopped
+    pub daemon: bool,
+}
+
+// The host guarantees that notify may be called from any thread.
+unsafe impl Send for HostCallbacks {}
+unsafe impl Sync for HostCallbacks {}
+
+impl HostCallbacks {
+    pub fn notify(&self) {
+        (self.notify)(self.context)
+    }
+}
+
+/// The functions every plugin provides.
+/// Slices and strings returned by the plugin stay valid until the next call with the same state.
+///
+/// The state is owned by the host and released with `free`, after which the library is unloaded.
+/// Memory is always released by the side that allocated it.
+#[repr(C)]
+pub struct PluginVTable {
+    /// Name of the plugin, shown above its errors
+    pub name: extern "C" fn() -> FfiStr,
+    /// Creates the state of the plugin, `config` is the entire OxiRun config serialized as TOML
+    pub model: unsafe extern "C" fn(config: FfiStr, host: HostCallbacks) -> PluginState,
+    /// Releases the state created by `model`, the state must not be used afterwards.
+    /// Threads started by the plugin have to be finished when this returns.
+    pub free: unsafe extern "C" fn(state: PluginState),
+    /// Handles pending work after the plugin called [`HostCallbacks::notify`]
+    pub update: unsafe extern "C" fn(state: PluginState),
+    /// Filters the entries of the plugin with the text entered by the user
+    pub query: unsafe extern "C" fn(state: PluginState, filter_text: FfiStr),
+    /// Returns the entries that should currently be shown
+    pub entries: unsafe extern "C" fn(state: PluginState) -> FfiSlice<FfiEntry>,
+    /// Launches the entry with the given id, the id is the one returned by `entries`.
+    /// `activation_token` was issued by the compositor for this launch, empty if none was issued.
+    pub launch: unsafe extern "C" fn(state: PluginState, entry_id: u64, activation_token: FfiStr),
+    pub errors: unsafe extern "C" fn(state: PluginState) -> FfiSlice<FfiStr>,
+    /// Applies a changed config, `config` is the entire OxiRun config serialized as TOML.
+    /// Returns false if the config could not be applied. Without this function, or when it
+    /// returns false, the host frees the state and creates a new one with the changed config.
+    pub reconfigure: Option<unsafe extern "C" fn(state: PluginState, config: FfiStr) -> bool>,
+    /// Names of the actions the plugin offers, users can bind them to keys
+    pub actions: unsafe extern "C" fn(state: PluginState) -> FfiSlice<FfiStr>,
+    /// Runs the action with the given name on the entry with the given id
+    pub run_action: unsafe extern "C" fn(state: PluginState, action: FfiStr, entry_id: u64),
+    /// Returns the actions offered for the entry with the given id, shown in its action menu
+    pub entry_actions:
+        unsafe extern "C" fn(state: PluginState, entry_id: u64) -> FfiSlice<FfiEntryAction>,
+    /// Launches the action with the given id of the entry with the given id, `activation_token` is
+    /// the same as for `launch`. Returns whether something was launched, the host only closes
+    /// then and queries the plugin again otherwise.
+    pub launch_action: unsafe extern "C" fn(
+        state: PluginState,
+        entry_id: u64,
+        action_id: u64,
+        activation_token: FfiStr,
+    ) -> bool,
+    /// Called instead of `query` when the filter text is empty
+    pub no_query: unsafe extern "C" fn(state: PluginState),
+}
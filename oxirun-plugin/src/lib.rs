@@ -0,0 +1,161 @@
+//! Everything needed to write an OxiRun plugin.
+//!
+//! Implement [`Plugin`] for the model of your plugin and export it with [`export_plugin!`],
+//! the macro generates the symbols OxiRun looks up when loading the library.
+//! ```ignore
+//! struct Model { errors: Vec<String> }
+//!
+//! impl Plugin for Model {
+//!     type Message = ();
+//!     const NAME: &'static str = "Example";
+//!     // ...
+//! }
+//!
+//! export_plugin!(Model);
+//! ```
+
+use std::{path::Path, sync::mpsc::Sender};
+
+pub use toml;
+use toml::Table;
+
+use crate::abi::HostCallbacks;
+
+pub mod abi;
+#[doc(hidden)]
+pub mod shim;
+
+/// A single entry shown by OxiRun.
+#[derive(Debug, Clone)]
+pub struct Entry<'a> {
+    /// Stable id of the entry, passed back to [`Plugin::launch`]
+    pub id: u64,
+    /// Score used to rank this entry against the entries of all plugins
+    pub score: i64,
+    pub title: &'a str,
+    pub subtitle: Option<&'a str>,
+    /// Path to a png or svg file
+    pub icon: Option<&'a Path>,
+}
+
+/// An action offered for a single entry, shown in the action menu of the entry.
+#[derive(Debug, Clone)]
+pub struct EntryAction<'a> {
+    /// Id of the action, passed back to [`Plugin::launch_action`]
+    pub id: u64,
+    pub title: &'a str,
+    /// Path to a png or svg file
+    pub icon: Option<&'a Path>,
+}
+
+/// Allows background work of the plugin to hand messages back to [`Plugin::update`].
+pub struct Context<Message> {
+    sender: Sender<Message>,
+    host: HostCallbacks,
+}
+
+impl<Message> Clone for Context<Message> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            host: self.host,
+        }
+    }
+}
+
+impl<Message> Context<Message> {
+    /// Queues the message and wakes up OxiRun, which then calls [`Plugin::update`] with it.
+    /// This can be called from any thread.
+    pub fn send(&self, msg: Message) {
+        if self.sender.send(msg).is_ok() {
+            self.host.notify();
+        }
+    }
+
+    /// Whether OxiRun runs as daemon, work like watching for changes only pays off then
+    pub fn daemon(&self) -> bool {
+        self.host.daemon
+    }
+}
+
+/// An OxiRun plugin, the implementing type is the model of the plugin.
+///
+/// The model is dropped when OxiRun releases the plugin, the library is unloaded right after.
+/// Threads started by the plugin therefore have to be finished in [`Drop`].
+pub trait Plugin: Sized + 'static {
+    type Message: Send + 'static;
+    /// Name of the plugin, shown above its errors
+    const NAME: &'static str;
+
+    /// Creates the model, `config` is the entire OxiRun config.
+    /// Expensive work should be moved to a thread which reports back via the context.
+    fn new(config: Table, context: Context<Self::Message>) -> Self;
+    /// Handles a message sent via [`Context::send`]
+    fn update(&mut self, msg: Self::Message);
+    /// Filters the entries of the plugin with the text entered by the user
+    fn query(&mut self, filter_text: &str);
+    /// Called instead of [`Plugin::query`] when nothing is entered, e.g. when OxiRun opens.
+    /// Plugins not implementing this receive an empty query.
+    fn no_query(&mut self) {
+        self.query("");
+    }
+    /// Launches the entry with the given id, the id is the one returned by `entries`.
+    /// The compositor only focuses launched applications presenting the activation token, pass
+    /// it on in `XDG_ACTIVATION_TOKEN`. It is None when the compositor did not issue one.
+    fn launch(&mut self, entry_id: u64, activation_token: Option<&str>);
+    /// Returns the entries that should currently be shown
+    fn entries(&self) -> Vec<Entry<'_>>;
+    fn errors(&self) -> &[String];
+    /// Applies a changed config, `config` is the entire OxiRun config.
+    /// Returning false makes OxiRun drop the model and create a new one with [`Plugin::new`],
+    /// which is what happens for plugins not implementing this.
+    fn reconfigure(&mut self, config: Table) -> bool {
+        let _ = config;
+        false
+    }
+    /// Names of the actions this plugin offers, users can bind them in `[keybindings]`
+    fn actions(&self) -> Vec<&str> {
+        Vec::new()
+    }
+    /// Runs one of the [`Plugin::actions`] on the focused entry, which belongs to this plugin
+    fn run_action(&mut self, action: &str, entry_id: u64) {
+        let _ = (action, entry_id);
+    }
+    /// Returns the actions offered for the entry with the given id, the first one is used as
+    /// its secondary action
+    fn entry_actions(&self, entry_id: u64) -> Vec<EntryAction<'_>> {
+        let _ = entry_id;
+        Vec::new()
+    }
+    /// Launches one of the [`Plugin::entry_actions`] of the entry with the given id, the
+    /// activation token is the same as for [`Plugin::launch`].
+    /// Returns whether something was launched, OxiRun only closes then. Actions which change the
+    /// entries instead, like pinning one, return false and are queried again.
+    fn launch_action(
+        &mut self,
+        entry_id: u64,
+        action_id: u64,
+        activation_token: Option<&str>,
+    ) -> bool {
+        let _ = (entry_id, action_id, activation_token);
+        false
+    }
+}
+
+/// Generates the symbols OxiRun requires to load the given [`Plugin`] from a library.
+#[macro_export]
+macro_rules! export_plugin {
+    ($plugin:ty) => {
+        static OXIRUN_PLUGIN_VTABLE: $crate::abi::PluginVTable = $crate::shim::vtable::<$plugin>();
+
+        #[unsafe(no_mangle)]
+        pub extern "C" fn oxirun_abi_version() -> u32 {
+            $crate::abi::ABI_VERSION
+        }
+
+        #[unsafe(no_mangle)]
+        pub extern "C" fn oxirun_plugin_vtable() -> &'static $crate::abi::PluginVTable {
+            &OXIRUN_PLUGIN_VTABLE
+        }
+    };
+}
@@ -0,0 +1,193 @@
+//! The ABI functions generated for every [`Plugin`], used by [`export_plugin!`](crate::export_plugin).
+
+use std::sync::mpsc::{self, Receiver};
+
+use crate::{
+    Context, Plugin,
+    abi::{FfiEntry, FfiEntryAction, FfiSlice, FfiStr, HostCallbacks, PluginState, PluginVTable},
+};
+
+struct Instance<P: Plugin> {
+    plugin: P,
+    receiver: Receiver<P::Message>,
+    // the views point into the plugin and are rebuilt on every request of the host
+    entry_views: Vec<FfiEntry>,
+    error_views: Vec<FfiStr>,
+    action_views: Vec<FfiStr>,
+    entry_action_views: Vec<FfiEntryAction>,
+}
+
+unsafe fn instance<'a, P: Plugin>(state: PluginState) -> &'a mut Instance<P> {
+    unsafe { &mut *(state as *mut Instance<P>) }
+}
+
+pub const fn vtable<P: Plugin>() -> PluginVTable {
+    PluginVTable {
+        name: name::<P>,
+        model: model::<P>,
+        free: free::<P>,
+        update: update::<P>,
+        query: query::<P>,
+        entries: entries::<P>,
+        launch: launch::<P>,
+        errors: errors::<P>,
+        reconfigure: Some(reconfigure::<P>),
+        actions: actions::<P>,
+        run_action: run_action::<P>,
+        entry_actions: entry_actions::<P>,
+        launch_action: launch_action::<P>,
+        no_query: no_query::<P>,
+    }
+}
+
+extern "C" fn name<P: Plugin>() -> FfiStr {
+    FfiStr::new(P::NAME)
+}
+
+unsafe extern "C" fn model<P: Plugin>(config: FfiStr, host: HostCallbacks) -> PluginState {
+    let config = unsafe { config.as_str() };
+    let (sender, receiver) = mpsc::channel();
+    let plugin = P::new(
+        toml::from_str(config).unwrap_or_default(),
+        Context { sender, host },
+    );
+    Box::into_raw(Box::new(Instance {
+        plugin,
+        receiver,
+        entry_views: Vec::new(),
+        error_views: Vec::new(),
+        action_views: Vec::new(),
+        entry_action_views: Vec::new(),
+    })) as PluginState
+}
+
+unsafe extern "C" fn free<P: Plugin>(state: PluginState) {
+    drop(unsafe { Box::from_raw(state as *mut Instance<P>) });
+}
+
+unsafe extern "C" fn update<P: Plugin>(state: PluginState) {
+    let instance = unsafe { instance::<P>(state) };
+    while let Ok(msg) = instance.receiver.try_recv() {
+        instance.plugin.update(msg);
+    }
+}
+
+unsafe extern "C" fn query<P: Plugin>(state: PluginState, filter_text: FfiStr) {
+    let instance = unsafe { instance::<P>(state) };
+    instance.plugin.query(unsafe { filter_text.as_str() });
+}
+
+unsafe extern "C" fn no_query<P: Plugin>(state: PluginState) {
+    let instance = unsafe { instance::<P>(state) };
+    instance.plugin.no_query();
+}
+
+/// Empty strings stand for a missing activation token
+unsafe fn activation_token<'a>(token: FfiStr) -> Option<&'a str> {
+    Some(unsafe { token.as_str() }).filter(|token| !token.is_empty())
+}
+
+unsafe extern "C" fn launch<P: Plugin>(
+    state: PluginState,
+    entry_id: u64,
+    activation_token: FfiStr,
+) {
+    let instance = unsafe { instance::<P>(state) };
+    instance.plugin.launch(entry_id, unsafe {
+        self::activation_token(activation_token)
+    });
+}
+
+unsafe extern "C" fn entries<P: Plugin>(state: PluginState) -> FfiSlice<FfiEntry> {
+    let instance = unsafe { instance::<P>(state) };
+    instance.entry_views = instance
+        .plugin
+        .entries()
+        .into_iter()
+        .map(|entry| FfiEntry {
+            id: entry.id,
+            score: entry.score,
+            title: FfiStr::new(entry.title),
+            subtitle: FfiStr::new(entry.subtitle.unwrap_or_default()),
+            icon: FfiStr::new(
+                entry
+                    .icon
+                    .and_then(|path| path.to_str())
+                    .unwrap_or_default(),
+            ),
+        })
+        .collect();
+    FfiSlice::new(&instance.entry_views)
+}
+
+unsafe extern "C" fn errors<P: Plugin>(state: PluginState) -> FfiSlice<FfiStr> {
+    let instance = unsafe { instance::<P>(state) };
+    instance.error_views = instance
+        .plugin
+        .errors()
+        .iter()
+        .map(|error| FfiStr::new(error))
+        .collect();
+    FfiSlice::new(&instance.error_views)
+}
+
+unsafe extern "C" fn reconfigure<P: Plugin>(state: PluginState, config: FfiStr) -> bool {
+    let instance = unsafe { instance::<P>(state) };
+    match toml::from_str(unsafe { config.as_str() }) {
+        Ok(config) => instance.plugin.reconfigure(config),
+        Err(_) => false,
+    }
+}
+
+unsafe extern "C" fn actions<P: Plugin>(state: PluginState) -> FfiSlice<FfiStr> {
+    let instance = unsafe { instance::<P>(state) };
+    instance.action_views = instance
+        .plugin
+        .actions()
+        .into_iter()
+        .map(FfiStr::new)
+        .collect();
+    FfiSlice::new(&instance.action_views)
+}
+
+unsafe extern "C" fn run_action<P: Plugin>(state: PluginState, action: FfiStr, entry_id: u64) {
+    let instance = unsafe { instance::<P>(state) };
+    instance
+        .plugin
+        .run_action(unsafe { action.as_str() }, entry_id);
+}
+
+unsafe extern "C" fn entry_actions<P: Plugin>(
+    state: PluginState,
+    entry_id: u64,
+) -> FfiSlice<FfiEntryAction> {
+    let instance = unsafe { instance::<P>(state) };
+    instance.entry_action_views = instance
+        .plugin
+        .entry_actions(entry_id)
+        .into_iter()
+        .map(|action| FfiEntryAction {
+            id: action.id,
+            title: FfiStr::new(action.title),
+            icon: FfiStr::new(
+                action
+                    .icon
+                    .and_then(|path| path.to_str())
+                    .unwrap_or_default(),
+            ),
+        })
+        .collect();
+    FfiSlice::new(&instance.entry_action_views)
+}
+
+unsafe extern "C" fn launch_action<P: Plugin>(
+    state: PluginState,
+    entry_id: u64,
+    action_id: u64,
+    activation_token: FfiStr,
+) -> bool {
+    let instance = unsafe { instance::<P>(state) };
+    instance.plugin.launch_action(entry_id, action_id, unsafe {
+        self::activation_token(activation_token)
+    })
+}
@@ -0,0 +1,155 @@
+//! Drives a plugin through its vtable like OxiRun does and checks that no memory is leaked.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    ffi::c_void,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use oxirun_plugin::{
+    Context, Entry, Plugin,
+    abi::{FfiStr, HostCallbacks, PluginVTable},
+    export_plugin,
+    toml::Table,
+};
+
+/// Counts the bytes currently allocated by the thread, the test harness allocates on others
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATED: Cell<isize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.with(|allocated| allocated.set(allocated.get() + layout.size() as isize));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.with(|allocated| allocated.set(allocated.get() - layout.size() as isize));
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocated() -> isize {
+    ALLOCATED.with(Cell::get)
+}
+
+static DROPPED_MODELS: AtomicUsize = AtomicUsize::new(0);
+
+struct Model {
+    entries: Vec<String>,
+    errors: Vec<String>,
+    _context: Context<()>,
+}
+
+impl Drop for Model {
+    fn drop(&mut self) {
+        DROPPED_MODELS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Plugin for Model {
+    type Message = ();
+    const NAME: &'static str = "Memory";
+
+    fn new(_config: Table, context: Context<()>) -> Self {
+        Self {
+            entries: Vec::new(),
+            errors: vec![String::from("error")],
+            _context: context,
+        }
+    }
+
+    fn update(&mut self, _msg: ()) {}
+
+    fn query(&mut self, filter_text: &str) {
+        self.entries = (0..20)
+            .map(|index| format!("{filter_text} {index}"))
+            .collect();
+    }
+
+    fn launch(&mut self, _entry_id: u64, _activation_token: Option<&str>) {}
+
+    fn entries(&self) -> Vec<Entry<'_>> {
+        self.entries
+            .iter()
+            .zip(0..)
+            .map(|(title, id)| Entry {
+                id,
+                score: 0,
+                title,
+                subtitle: Some("subtitle"),
+                icon: None,
+            })
+            .collect()
+    }
+
+    fn errors(&self) -> &[String] {
+        &self.errors
+    }
+}
+
+export_plugin!(Model);
+
+extern "C" fn notify(_context: *mut c_void) {}
+
+fn host() -> HostCallbacks {
+    HostCallbacks {
+        context: std::ptr::null_mut(),
+        notify,
+        daemon: false,
+    }
+}
+
+fn run_queries(vtable: &PluginVTable, count: usize) {
+    let state = unsafe { (vtable.model)(FfiStr::empty(), host()) };
+    for index in 0..count {
+        // the filter text grows and shrinks again, like typing and deleting
+        let filter_text = "x".repeat(index % 10);
+        unsafe {
+            (vtable.query)(state, FfiStr::new(&filter_text));
+            (vtable.update)(state);
+            assert_eq!((vtable.entries)(state).as_slice().len(), 20);
+            assert_eq!((vtable.errors)(state).as_slice().len(), 1);
+        }
+    }
+    unsafe { (vtable.free)(state) };
+}
+
+#[test]
+fn memory_stays_flat() {
+    let vtable = oxirun_plugin_vtable();
+
+    // warms up everything allocated once per thread
+    run_queries(vtable, 10);
+    let before = allocated();
+    let dropped_before = DROPPED_MODELS.load(Ordering::SeqCst);
+
+    run_queries(vtable, 10_000);
+    assert_eq!(
+        DROPPED_MODELS.load(Ordering::SeqCst),
+        dropped_before + 1,
+        "free has to drop the model"
+    );
+    assert_eq!(allocated(), before, "queries leaked memory");
+
+    for _ in 0..1_000 {
+        run_queries(vtable, 5);
+    }
+    assert_eq!(
+        DROPPED_MODELS.load(Ordering::SeqCst),
+        dropped_before + 1_001,
+        "free has to drop every model"
+    );
+    assert_eq!(
+        allocated(),
+        before,
+        "creating and freeing models leaked memory"
+    );
+}
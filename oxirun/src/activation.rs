@@ -0,0 +1,86 @@
+//! Requests activation tokens with the xdg_activation_v1 protocol.
+//!
+//! Compositors only grant focus to launched applications presenting a token issued for the
+//! launch, the token OxiRun was started with has already been used for its own window.
+//! The token is requested over a connection of its own, the surfaces of iced are not reachable
+//! from here, which means the compositor can not tie it to the last input event.
+
+use wayland_client::{
+    Connection, Dispatch, QueueHandle,
+    globals::{GlobalListContents, registry_queue_init},
+    protocol::wl_registry::WlRegistry,
+};
+use wayland_protocols::xdg::activation::v1::client::{
+    xdg_activation_token_v1::{self, XdgActivationTokenV1},
+    xdg_activation_v1::XdgActivationV1,
+};
+
+#[derive(Default)]
+struct State {
+    token: Option<String>,
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _state: &mut Self,
+        _registry: &WlRegistry,
+        _event: <WlRegistry as wayland_client::Proxy>::Event,
+        _data: &GlobalListContents,
+        _connection: &Connection,
+        _queue: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<XdgActivationV1, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _activation: &XdgActivationV1,
+        _event: <XdgActivationV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _connection: &Connection,
+        _queue: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<XdgActivationTokenV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _token: &XdgActivationTokenV1,
+        event: xdg_activation_token_v1::Event,
+        _data: &(),
+        _connection: &Connection,
+        _queue: &QueueHandle<Self>,
+    ) {
+        if let xdg_activation_token_v1::Event::Done { token } = event {
+            state.token = Some(token);
+        }
+    }
+}
+
+/// Requests a new activation token from the compositor, every launch needs its own
+pub fn activation_token() -> Result<String, String> {
+    let connection = Connection::connect_to_env()
+        .map_err(|error| format!("Could not connect to the compositor: {error}"))?;
+    let (globals, mut queue) = registry_queue_init::<State>(&connection)
+        .map_err(|error| format!("Could not connect to the compositor: {error}"))?;
+    let handle = queue.handle();
+    let activation: XdgActivationV1 = globals
+        .bind(&handle, 1..=1, ())
+        .map_err(|error| format!("The compositor does not support activation: {error}"))?;
+    let token = activation.get_activation_token(&handle, ());
+    token.set_app_id(String::from("oxirun"));
+    token.commit();
+    let mut state = State::default();
+    // compositors send the token in response to the commit, which the roundtrip waits for
+    let result = queue
+        .roundtrip(&mut state)
+        .map_err(|error| format!("Could not request an activation token: {error}"));
+    token.destroy();
+    activation.destroy();
+    result?;
+    state
+        .token
+        .ok_or_else(|| String::from("The compositor did not issue an activation token"))
+}
@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::config::WindowAnchor;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "A simple application runner made with Iced")]
+pub struct Cli {
+    /// Keep OxiRun running in the background, the window is opened with `oxirun show`
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Config file to use instead of `$XDG_CONFIG_HOME/oxirun/config.toml`
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Directory the plugins are loaded from instead of `$XDG_CONFIG_HOME/oxirun/plugins`
+    #[arg(long)]
+    pub plugin_dir: Option<PathBuf>,
+
+    /// Plugins to load, overrides `plugins` of the config
+    #[arg(long, value_delimiter = ',')]
+    pub plugins: Option<Vec<String>>,
+
+    /// Edges the window is anchored to, overrides `window.anchor` of the config
+    #[arg(long, value_delimiter = ',')]
+    pub anchor: Option<Vec<WindowAnchor>>,
+
+    /// Width of the window, overrides `window.width` of the config
+    #[arg(long)]
+    pub width: Option<u32>,
+
+    /// Height of the window, overrides `window.height` of the config
+    #[arg(long)]
+    pub height: Option<u32>,
+
+    /// Placeholder shown in the search box
+    #[arg(short, long)]
+    pub prompt: Option<String>,
+
+    /// Text the search box starts with
+    #[arg(short, long)]
+    pub query: Option<String>,
+
+    #[command(flatten)]
+    pub dmenu: DmenuArgs,
+
+    #[command(subcommand)]
+    pub command: Option<DaemonCommand>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct DmenuArgs {
+    /// Offer the lines read from stdin and print the selected one to stdout.
+    /// Prints the entered text when nothing matches, exits with 1 when cancelled
+    #[arg(long, conflicts_with = "daemon")]
+    pub dmenu: bool,
+
+    /// Match case insensitively in dmenu mode
+    #[arg(short, long, requires = "dmenu")]
+    pub insensitive: bool,
+
+    /// Print the index of the selected line instead of the line itself
+    #[arg(long, requires = "dmenu")]
+    pub index: bool,
+
+    /// Allow selecting multiple lines with shift+enter, each selected line is printed
+    #[arg(long, requires = "dmenu")]
+    pub multi_select: bool,
+
+    /// Amount of matching lines offered in dmenu mode, all of them by default
+    #[arg(short, long, requires = "dmenu")]
+    pub lines: Option<usize>,
+}
+
+/// Commands sent to a running daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Subcommand)]
+pub enum DaemonCommand {
+    /// Shows the window of the daemon, or hides it when it is already shown
+    Toggle,
+    /// Shows the window of the daemon
+    Show,
+    /// Hides the window of the daemon
+    Hide,
+}
+
+impl DaemonCommand {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DaemonCommand::Toggle => "toggle",
+            DaemonCommand::Show => "show",
+            DaemonCommand::Hide => "hide",
+        }
+    }
+
+    pub fn parse(command: &str) -> Option<Self> {
+        match command {
+            "toggle" => Some(DaemonCommand::Toggle),
+            "show" => Some(DaemonCommand::Show),
+            "hide" => Some(DaemonCommand::Hide),
+            _ => None,
+        }
+    }
+}
@@ -1,8 +1,182 @@
-use std::{fs, io::Read, path::PathBuf};
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
 
-use iced_layershell::reexport::Anchor;
+use clap::ValueEnum;
+use iced_layershell::reexport::{Anchor, KeyboardInteractivity, Layer};
+use optional_struct::{Applicable, optional_struct};
+use serde::{Deserialize, Serialize};
 use toml::Table;
 
+use crate::cli::Cli;
+
+/// Adjusts the scores of a plugin before they are ranked together with all other plugins.
+/// The final score is `score * weight + offset`.
+#[optional_struct]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Ranking {
+    pub weight: f64,
+    pub offset: i64,
+}
+
+impl Default for Ranking {
+    fn default() -> Self {
+        Self {
+            weight: 1.0,
+            offset: 0,
+        }
+    }
+}
+
+impl Ranking {
+    pub fn apply(&self, score: i64) -> i64 {
+        (score as f64 * self.weight) as i64 + self.offset
+    }
+}
+
+/// The `[window]` section of the config.
+#[optional_struct]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+    /// Margins in the order top, right, bottom, left
+    pub margins: [i32; 4],
+    /// Edges the window is anchored to, an empty list centers the window
+    pub anchor: Vec<WindowAnchor>,
+    pub layer: WindowLayer,
+    pub keyboard_mode: WindowKeyboardMode,
+    pub icon_size: f32,
+    pub subtitle_size: f32,
+    pub scale: f32,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            width: 600,
+            height: 600,
+            margins: [100, 100, 100, 100],
+            anchor: Vec::new(),
+            layer: WindowLayer::Overlay,
+            keyboard_mode: WindowKeyboardMode::Exclusive,
+            icon_size: 50.0,
+            subtitle_size: 12.0,
+            scale: 1.0,
+        }
+    }
+}
+
+impl WindowConfig {
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn margins(&self) -> (i32, i32, i32, i32) {
+        let [top, right, bottom, left] = self.margins;
+        (top, right, bottom, left)
+    }
+
+    pub fn anchor(&self) -> Anchor {
+        self.anchor
+            .iter()
+            .fold(Anchor::empty(), |anchor, edge| anchor | Anchor::from(*edge))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowAnchor {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl From<WindowAnchor> for Anchor {
+    fn from(anchor: WindowAnchor) -> Self {
+        match anchor {
+            WindowAnchor::Top => Anchor::Top,
+            WindowAnchor::Bottom => Anchor::Bottom,
+            WindowAnchor::Left => Anchor::Left,
+            WindowAnchor::Right => Anchor::Right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowLayer {
+    Background,
+    Bottom,
+    Top,
+    Overlay,
+}
+
+impl From<WindowLayer> for Layer {
+    fn from(layer: WindowLayer) -> Self {
+        match layer {
+            WindowLayer::Background => Layer::Background,
+            WindowLayer::Bottom => Layer::Bottom,
+            WindowLayer::Top => Layer::Top,
+            WindowLayer::Overlay => Layer::Overlay,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowKeyboardMode {
+    None,
+    Exclusive,
+    OnDemand,
+}
+
+impl From<WindowKeyboardMode> for KeyboardInteractivity {
+    fn from(mode: WindowKeyboardMode) -> Self {
+        match mode {
+            WindowKeyboardMode::None => KeyboardInteractivity::None,
+            WindowKeyboardMode::Exclusive => KeyboardInteractivity::Exclusive,
+            WindowKeyboardMode::OnDemand => KeyboardInteractivity::OnDemand,
+        }
+    }
+}
+
+/// Reads the `[window]` section, values passed on the command line replace the configured ones.
+/// Invalid values are reported and replaced by the defaults.
+pub fn get_window_config(config: &Table, cli: &Cli) -> (WindowConfig, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut window_config = WindowConfig::default();
+    // the anchor used to be configured at the top level, `window.anchor` takes precedence
+    if let Some(anchor_value) = config.get("anchor") {
+        match anchor_value.clone().try_into::<Vec<WindowAnchor>>() {
+            Ok(anchor) => window_config.anchor = anchor,
+            Err(error) => errors.push(format!("Invalid anchor: {}", error.to_string().trim_end())),
+        }
+    }
+    if let Some(window_value) = config.get("window") {
+        match window_value.clone().try_into::<OptionalWindowConfig>() {
+            Ok(optional_config) => window_config = optional_config.build(window_config),
+            Err(error) => errors.push(format!(
+                "Invalid [window] config: {}",
+                error.to_string().trim_end()
+            )),
+        }
+    }
+    if let Some(anchor) = &cli.anchor {
+        window_config.anchor = anchor.clone();
+    }
+    if let Some(width) = cli.width {
+        window_config.width = width;
+    }
+    if let Some(height) = cli.height {
+        window_config.height = height;
+    }
+    (window_config, errors)
+}
+
 pub fn get_allowed_plugins(config: &Table) -> Vec<&str> {
     match config.get("plugins") {
         Some(toml::Value::Array(values)) => values
@@ -16,52 +190,154 @@ pub fn get_allowed_plugins(config: &Table) -> Vec<&str> {
     }
 }
 
-fn read_config(oxirun_config: &PathBuf) -> Table {
-    let mut read_config = String::new();
-    let mut file = fs::File::open(oxirun_config).expect("Could not open config file");
-    let _ = file
-        .read_to_string(&mut read_config)
-        .expect("Could not read config file");
+/// Returns the ranking of the plugin with the given file name.
+/// Configured via `[ranking."<plugin file name>"]`, missing values use the defaults.
+/// An invalid section is reported and replaced by the defaults.
+pub fn get_plugin_ranking(config: &Table, plugin_file: &str) -> (Ranking, Option<String>) {
+    let default_ranking = Ranking::default();
+    let ranking_value = config
+        .get("ranking")
+        .and_then(|ranking| ranking.get(plugin_file));
+    if let Some(ranking_value) = ranking_value {
+        match ranking_value.clone().try_into::<OptionalRanking>() {
+            Ok(ranking) => (ranking.build(default_ranking), None),
+            Err(error) => (
+                default_ranking,
+                Some(format!(
+                    "Invalid [ranking.\"{plugin_file}\"] config: {}",
+                    error.to_string().trim_end()
+                )),
+            ),
+        }
+    } else {
+        (default_ranking, None)
+    }
+}
 
-    toml::from_str(&read_config).expect("Could not deserialize config")
+/// An error encountered while reading the config file.
+#[derive(Debug)]
+pub enum ConfigError {
+    Read {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    /// Line and column start at 1
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// Neither `XDG_CONFIG_HOME` nor `HOME` is set
+    NoConfigHome,
+    CreateDir {
+        path: PathBuf,
+        error: std::io::Error,
+    },
 }
 
-pub fn get_oxirun_dir() -> PathBuf {
-    let base_dirs = xdg::BaseDirectories::new();
-    let config_home = base_dirs.get_config_home();
-    let oxirun_dir = config_home
-        .expect("Could not get config home")
-        .join("oxirun");
-    if !oxirun_dir.is_dir() {
-        std::fs::create_dir(&oxirun_dir).expect("Could not create config dir");
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Read { path, error } => {
+                write!(f, "Could not read {}: {error}", path.display())
+            }
+            ConfigError::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{line}:{column}: {message}", path.display()),
+            ConfigError::NoConfigHome => {
+                write!(f, "Could not find the config directory, HOME is not set")
+            }
+            ConfigError::CreateDir { path, error } => {
+                write!(f, "Could not create {}: {error}", path.display())
+            }
+        }
     }
-    oxirun_dir
 }
 
-pub fn get_config() -> Table {
-    let oxirun_dir = get_oxirun_dir();
-    let oxirun_config = oxirun_dir.join("config.toml");
-    if !oxirun_config.is_file() {
-        Table::new()
-    } else {
-        read_config(&oxirun_config)
+/// Translates a byte offset into a line and column, both starting at 1
+fn line_and_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+fn read_config(oxirun_config: &Path) -> Result<Table, ConfigError> {
+    let content = fs::read_to_string(oxirun_config).map_err(|error| ConfigError::Read {
+        path: oxirun_config.to_path_buf(),
+        error,
+    })?;
+    toml::from_str(&content).map_err(|error| {
+        let offset = error.span().map_or(0, |span| span.start);
+        let (line, column) = line_and_column(&content, offset);
+        ConfigError::Parse {
+            path: oxirun_config.to_path_buf(),
+            line,
+            column,
+            message: error.message().to_string(),
+        }
+    })
+}
+
+fn create_dir(dir: &Path) -> Result<(), ConfigError> {
+    if dir.is_dir() {
+        return Ok(());
+    }
+    fs::create_dir_all(dir).map_err(|error| ConfigError::CreateDir {
+        path: dir.to_path_buf(),
+        error,
+    })
+}
+
+/// `$XDG_CONFIG_HOME/oxirun`, created if it does not exist yet
+pub fn get_oxirun_dir() -> Result<PathBuf, ConfigError> {
+    let oxirun_dir = xdg::BaseDirectories::new()
+        .get_config_home()
+        .ok_or(ConfigError::NoConfigHome)?
+        .join("oxirun");
+    create_dir(&oxirun_dir)?;
+    Ok(oxirun_dir)
+}
+
+/// The config file passed on the command line, or `$XDG_CONFIG_HOME/oxirun/config.toml`
+pub fn config_path(cli: &Cli) -> Result<PathBuf, ConfigError> {
+    match &cli.config {
+        Some(oxirun_config) => Ok(oxirun_config.clone()),
+        None => get_oxirun_dir().map(|oxirun_dir| oxirun_dir.join("config.toml")),
     }
 }
 
-fn anchor_from_string(anchor_str: &str) -> Anchor {
-    match anchor_str.to_lowercase().as_str() {
-        "top" => Anchor::Top,
-        "bottom" => Anchor::Bottom,
-        "right" => Anchor::Right,
-        "left" => Anchor::Left,
-        _ => Anchor::Top,
+/// Reads the config file passed on the command line, or the default one if it exists.
+/// Values passed on the command line replace the ones of the file.
+/// A config file which can not be read is reported and replaced by an empty config.
+pub fn get_config(cli: &Cli) -> (Table, Option<ConfigError>) {
+    let config_res = config_path(cli).and_then(|oxirun_config| {
+        if cli.config.is_none() && !oxirun_config.is_file() {
+            Ok(Table::new())
+        } else {
+            read_config(&oxirun_config)
+        }
+    });
+    let (mut config, error) = match config_res {
+        Ok(config) => (config, None),
+        Err(error) => (Table::new(), Some(error)),
+    };
+    if let Some(plugins) = &cli.plugins {
+        config.insert("plugins".into(), plugins.clone().into());
     }
+    (config, error)
 }
 
-pub fn anchor_from_strings(anchor_strs: Vec<&str>) -> Anchor {
-    let mut anchor = Anchor::empty();
-    for anchor_str in anchor_strs {
-        anchor |= anchor_from_string(anchor_str);
+/// The plugin dir passed on the command line, or `$XDG_CONFIG_HOME/oxirun/plugins`
+pub fn get_plugin_dir(cli: &Cli) -> Result<PathBuf, ConfigError> {
+    if let Some(plugin_dir) = &cli.plugin_dir {
+        return Ok(plugin_dir.clone());
     }
-    anchor
+    let plugin_dir = get_oxirun_dir()?.join("plugins");
+    create_dir(&plugin_dir)?;
+    Ok(plugin_dir)
 }
@@ -0,0 +1,70 @@
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use iced::futures::{
+    Stream, StreamExt,
+    channel::mpsc::{UnboundedReceiver, unbounded},
+    stream,
+};
+use once_cell::sync::Lazy;
+
+use crate::cli::DaemonCommand;
+
+/// Filled by [`start_listener`], turned into a subscription by [`daemon_commands`].
+static COMMANDS: Lazy<Mutex<Option<UnboundedReceiver<DaemonCommand>>>> =
+    Lazy::new(|| Mutex::new(None));
+
+pub fn socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("oxirun.sock")
+}
+
+/// Sends a command to the running daemon.
+pub fn send_command(command: DaemonCommand) -> std::io::Result<()> {
+    let mut stream = UnixStream::connect(socket_path())?;
+    writeln!(stream, "{}", command.as_str())
+}
+
+/// Binds the socket of the daemon, each line received on it is a single command.
+pub fn start_listener() -> std::io::Result<()> {
+    let path = socket_path();
+    if UnixStream::connect(&path).is_ok() {
+        return Err(std::io::Error::new(
+            ErrorKind::AddrInUse,
+            "OxiRun daemon is already running",
+        ));
+    }
+    // a socket file without a listener is left over from a daemon that was killed
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+
+    let (sender, receiver) = unbounded();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                if let Some(command) = DaemonCommand::parse(line.trim()) {
+                    let _ = sender.unbounded_send(command);
+                }
+            }
+        }
+    });
+    if let Ok(mut commands) = COMMANDS.lock() {
+        *commands = Some(receiver);
+    }
+    Ok(())
+}
+
+/// Yields every command received by the daemon, empty when OxiRun is not running as a daemon.
+pub fn daemon_commands() -> impl Stream<Item = DaemonCommand> {
+    let receiver = COMMANDS
+        .lock()
+        .ok()
+        .and_then(|mut receiver| receiver.take());
+    stream::iter(receiver).flatten()
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use iced::keyboard::{Key, Modifiers, key::Named};
+use toml::{Table, Value};
+
+/// Named keys which can be used in a chord, the names are matched case insensitively.
+const NAMED_KEYS: [(&str, Named); 14] = [
+    ("enter", Named::Enter),
+    ("escape", Named::Escape),
+    ("tab", Named::Tab),
+    ("space", Named::Space),
+    ("backspace", Named::Backspace),
+    ("delete", Named::Delete),
+    ("up", Named::ArrowUp),
+    ("down", Named::ArrowDown),
+    ("left", Named::ArrowLeft),
+    ("right", Named::ArrowRight),
+    ("pageup", Named::PageUp),
+    ("pagedown", Named::PageDown),
+    ("home", Named::Home),
+    ("end", Named::End),
+];
+
+const DEFAULT_KEYBINDINGS: [(&str, &str); 10] = [
+    ("escape", "exit"),
+    ("enter", "launch"),
+    ("shift+enter", "mark"),
+    ("up", "prev"),
+    ("shift+tab", "prev"),
+    ("down", "next"),
+    ("tab", "actions"),
+    ("right", "actions"),
+    ("ctrl+enter", "actions"),
+    ("left", "back"),
+];
+
+/// A key together with the modifiers held while pressing it, written as e.g. `ctrl+shift+j`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    key: String,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+    logo: bool,
+}
+
+impl KeyChord {
+    /// Returns None for keys which can not be bound
+    pub fn from_key(key: &Key, modifiers: Modifiers) -> Option<Self> {
+        let key = match key {
+            Key::Named(named) => NAMED_KEYS
+                .iter()
+                .find(|(_, candidate)| candidate == named)
+                .map(|(name, _)| name.to_string())?,
+            Key::Character(character) => character.to_lowercase(),
+            Key::Unidentified => return None,
+        };
+        Some(Self {
+            key,
+            ctrl: modifiers.control(),
+            alt: modifiers.alt(),
+            shift: modifiers.shift(),
+            logo: modifiers.logo(),
+        })
+    }
+
+    pub fn parse(chord: &str) -> Result<Self, String> {
+        let chord = chord.trim().to_lowercase();
+        let mut parts = chord.split('+').map(str::trim).collect::<Vec<_>>();
+        let key = parts.pop().unwrap_or_default();
+        let is_named = NAMED_KEYS.iter().any(|(name, _)| *name == key);
+        if !is_named && key.chars().count() != 1 {
+            return Err(format!("unknown key \"{key}\""));
+        }
+        let mut parsed = Self {
+            key: key.to_string(),
+            ctrl: false,
+            alt: false,
+            shift: false,
+            logo: false,
+        };
+        for modifier in parts {
+            match modifier {
+                "ctrl" | "control" => parsed.ctrl = true,
+                "alt" => parsed.alt = true,
+                "shift" => parsed.shift = true,
+                "super" | "logo" => parsed.logo = true,
+                _ => return Err(format!("unknown modifier \"{modifier}\"")),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// What happens when a bound chord is pressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Next,
+    Prev,
+    PageDown,
+    PageUp,
+    First,
+    Last,
+    Launch,
+    /// Launches the first entry action of the focused entry, or runs the first action its
+    /// plugin offers if the entry has none
+    Secondary,
+    /// Opens the action menu of the focused entry
+    Actions,
+    /// Closes the action menu
+    Back,
+    /// Selects the focused entry in multi select mode
+    Mark,
+    /// Clears the search box
+    Clear,
+    Exit,
+    /// An action offered by the plugin of the focused entry
+    Plugin(String),
+}
+
+impl Action {
+    pub fn parse(action: &str) -> Self {
+        match action {
+            "next" => Action::Next,
+            "prev" => Action::Prev,
+            "page_down" => Action::PageDown,
+            "page_up" => Action::PageUp,
+            "first" => Action::First,
+            "last" => Action::Last,
+            "launch" => Action::Launch,
+            "secondary" => Action::Secondary,
+            "actions" => Action::Actions,
+            "back" => Action::Back,
+            "mark" => Action::Mark,
+            "clear" => Action::Clear,
+            "exit" => Action::Exit,
+            _ => Action::Plugin(action.to_string()),
+        }
+    }
+}
+
+pub type Keybindings = HashMap<KeyChord, Action>;
+
+/// Returns the default keybindings with the ones of `[keybindings]` applied on top.
+/// Each entry maps a chord to an action, e.g. `"ctrl+j" = "next"`, invalid entries are reported
+/// and skipped.
+pub fn get_keybindings(config: &Table) -> (Keybindings, Vec<String>) {
+    let mut keybindings = DEFAULT_KEYBINDINGS
+        .iter()
+        .filter_map(|(chord, action)| Some((KeyChord::parse(chord).ok()?, Action::parse(action))))
+        .collect::<Keybindings>();
+    let mut errors = Vec::new();
+    let Some(configured) = config.get("keybindings") else {
+        return (keybindings, errors);
+    };
+    let Some(configured) = configured.as_table() else {
+        errors.push(String::from(
+            "Invalid [keybindings] config: expected a table",
+        ));
+        return (keybindings, errors);
+    };
+    for (chord, action) in configured {
+        let Some(action) = action.as_str() else {
+            errors.push(format!(
+                "Invalid keybinding \"{chord}\": expected an action name"
+            ));
+            continue;
+        };
+        match KeyChord::parse(chord) {
+            Ok(chord) => {
+                keybindings.insert(chord, Action::parse(action));
+            }
+            Err(error) => errors.push(format!("Invalid keybinding \"{chord}\": {error}")),
+        }
+    }
+    (keybindings, errors)
+}
+
+/// Reports the `[keybindings]` entries naming an action which is neither built in nor offered by
+/// one of the plugins, pressing them would do nothing
+pub fn unknown_actions(config: &Table, plugin_actions: &[String]) -> Vec<String> {
+    let Some(configured) = config.get("keybindings").and_then(Value::as_table) else {
+        return Vec::new();
+    };
+    configured
+        .iter()
+        // invalid entries are already reported by get_keybindings
+        .filter(|(chord, _)| KeyChord::parse(chord).is_ok())
+        .filter_map(|(chord, action)| Some((chord, action.as_str()?)))
+        .filter(|(_, action)| match Action::parse(action) {
+            Action::Plugin(name) => !plugin_actions.contains(&name),
+            _ => false,
+        })
+        .map(|(chord, action)| {
+            format!("Invalid keybinding \"{chord}\": unknown action \"{action}\"")
+        })
+        .collect()
+}
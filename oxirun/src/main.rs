@@ -1,69 +1,108 @@
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::io::BufRead;
 
-use config::{get_allowed_plugins, get_config, get_oxirun_dir};
-use iced::keyboard::Modifiers;
-use iced::keyboard::key::Named;
+use clap::Parser;
+use cli::{Cli, DaemonCommand};
+use config::{get_allowed_plugins, get_config, get_plugin_dir};
+use daemon::{daemon_commands, send_command, start_listener};
 use iced::theme::Style;
 use iced::widget::operation::focus;
-use iced::widget::{Column, Row, button, text};
-use iced::{Element, Length, Subscription, Task, Theme, event};
+use iced::widget::{Column, Row, button, container, image, mouse_area, svg, text};
+use iced::{Element, Length, Subscription, Task, Theme, event, window};
 use once_cell::sync::Lazy;
 use oxiced::theme::theme_impl::{OXITHEME, get_derived_iced_theme};
 use oxiced::widgets::oxi_button::{self, ButtonVariant};
 use oxiced::widgets::oxi_layer::{layer_theme, rounded_layer};
 use oxiced::widgets::oxi_text_input::text_input;
 
-use iced_layershell::reexport::{Anchor, KeyboardInteractivity, Layer};
-use iced_layershell::settings::{LayerShellSettings, Settings};
-use plugins::{PluginFuncs, PluginModel, PluginMsg, load_plugin};
+use iced_layershell::reexport::NewLayerShellSettings;
+use iced_layershell::settings::{LayerShellSettings, Settings, StartMode};
+use iced_layershell::to_layer_message;
+use keybindings::{Action, KeyChord, Keybindings, get_keybindings, unknown_actions};
+use plugins::{DmenuPlugin, Plugin, PluginEntry, load_plugin, plugin_notifications, spawn_plugin};
 use toml::Table;
 use utils::{FocusDirection, MEDIUM_SPACING};
+use watcher::config_changes;
 
-use crate::config::anchor_from_strings;
+use crate::activation::activation_token;
+use crate::config::{ConfigError, Ranking, WindowConfig, get_plugin_ranking, get_window_config};
 
+mod activation;
+mod cli;
 mod config;
+mod daemon;
+mod keybindings;
 mod plugins;
 mod utils;
+mod watcher;
 
-static CONFIG: Lazy<Table> = Lazy::new(get_config);
+static CLI: Lazy<Cli> = Lazy::new(Cli::parse);
+/// The config and the error encountered while reading it
+static CONFIG: Lazy<(Table, Option<ConfigError>)> = Lazy::new(|| get_config(&CLI));
 
-// TODO make this configurable
-const ICON_SIZE: f32 = 50.0;
-const SCALE_FACTOR: f32 = 1.0;
-const WINDOW_SIZE: (u32, u32) = (600, 600);
-const WINDOW_MARGINS: (i32, i32, i32, i32) = (100, 100, 100, 100);
-const WINDOW_LAYER: Layer = Layer::Overlay;
-const WINDOW_KEYBAORD_MODE: KeyboardInteractivity = KeyboardInteractivity::Exclusive;
+/// The `[window]` config and the errors encountered while reading it
+static WINDOW: Lazy<(WindowConfig, Vec<String>)> = Lazy::new(|| get_window_config(config(), &CLI));
+
+const DEFAULT_PROMPT: &str = "Enter text to find";
+/// The search box is given a fixed height, which makes the rows fitting below it known
+const SEARCH_BOX_HEIGHT: f32 = 40.0;
 
 pub fn main() -> Result<(), iced_layershell::Error> {
-    let default_anchor = Anchor::empty();
-    let binding = &CONFIG;
-    let anchor_opt = binding.get("anchor");
-    let anchor = if let Some(anchor_str) = anchor_opt {
-        anchor_from_strings(
-            anchor_str
-                .as_array()
-                .unwrap()
-                .iter()
-                .map(|value| value.as_str().unwrap_or("top"))
-                .collect::<Vec<_>>(),
+    let cli = &*CLI;
+    if let Some(command) = cli.command {
+        if let Err(error) = send_command(command) {
+            eprintln!("Could not reach the OxiRun daemon: {error}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if cli.daemon {
+        if let Err(error) = start_listener() {
+            eprintln!("Could not start the OxiRun daemon: {error}");
+            std::process::exit(1);
+        }
+        // the window is only created once the daemon receives a show command
+        let settings = Settings {
+            layer_settings: LayerShellSettings {
+                start_mode: StartMode::Background,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        return iced_layershell::daemon(
+            OxiRun::new_daemon,
+            OxiRun::namespace,
+            OxiRun::update,
+            OxiRun::view_window,
         )
-    } else {
-        default_anchor
-    };
+        .subscription(OxiRun::subscription)
+        .settings(settings)
+        .theme(OxiRun::theme)
+        .style(OxiRun::style)
+        .scale_factor(OxiRun::scale_factor)
+        .run();
+    }
+
     let settings = Settings {
         layer_settings: LayerShellSettings {
-            size: Some(WINDOW_SIZE),
+            size: Some(window_config().size()),
             exclusive_zone: 0,
-            anchor,
-            layer: WINDOW_LAYER,
-            margin: WINDOW_MARGINS,
-            keyboard_interactivity: WINDOW_KEYBAORD_MODE,
+            anchor: window_config().anchor(),
+            layer: window_config().layer.into(),
+            margin: window_config().margins(),
+            keyboard_interactivity: window_config().keyboard_mode.into(),
             ..Default::default()
         },
         ..Default::default()
     };
-    iced_layershell::application(OxiRun::new, OxiRun::namespace, OxiRun::update, OxiRun::view)
+    let boot = if cli.dmenu.dmenu {
+        OxiRun::new_dmenu
+    } else {
+        OxiRun::new
+    };
+    iced_layershell::application(boot, OxiRun::namespace, OxiRun::update, OxiRun::view)
         .subscription(OxiRun::subscription)
         .settings(settings)
         .theme(OxiRun::theme)
@@ -72,12 +111,55 @@ pub fn main() -> Result<(), iced_layershell::Error> {
         .run()
 }
 
+fn config() -> &'static Table {
+    &CONFIG.0
+}
+
+/// Errors of the config itself, shown above the errors of the plugins
+fn config_errors() -> Vec<String> {
+    CONFIG
+        .1
+        .iter()
+        .map(ToString::to_string)
+        .chain(WINDOW.1.iter().cloned())
+        .collect()
+}
+
+fn window_config() -> &'static WindowConfig {
+    &WINDOW.0
+}
+
 struct OxiRun {
     theme: Theme,
     filter_text: String,
-    plugins: HashMap<usize, (PluginModel, PluginFuncs)>,
+    /// Keyed by an id which stays the same while the plugin is loaded, even across config reloads
+    plugins: HashMap<usize, Box<dyn Plugin>>,
+    rankings: HashMap<usize, Ranking>,
+    /// File name and id of the loaded plugins, in the order of the `plugins` array
+    plugin_order: Vec<(String, usize)>,
+    next_plugin_id: usize,
+    results: Vec<ResultEntry>,
+    errors: Vec<String>,
     current_focus: usize,
-    _config: Table, // TODO use
+    /// Set when running as daemon, the window is hidden instead of exiting
+    daemon: bool,
+    /// The shown window, the one created by iced_layershell outside of daemon mode is only known
+    /// once it received an event
+    window: Option<window::Id>,
+    /// Set when running in dmenu mode, the selection is printed instead of launched
+    dmenu: bool,
+    multi_select: bool,
+    /// Plugin id and entry id of the entries selected in multi select mode
+    marked: Vec<(usize, u64)>,
+    prompt: String,
+    config: Table,
+    window_config: WindowConfig,
+    keybindings: Keybindings,
+    /// Keybindings naming an action no plugin offers, process plugins only report their actions
+    /// once they are running, which is why these are checked again whenever a plugin notifies
+    unknown_actions: Vec<String>,
+    /// Shown instead of the results while open
+    action_menu: Option<ActionMenu>,
 }
 
 impl Default for OxiRun {
@@ -86,79 +168,74 @@ impl Default for OxiRun {
             theme: get_derived_iced_theme(),
             filter_text: "".into(),
             plugins: HashMap::new(),
+            rankings: HashMap::new(),
+            plugin_order: Vec::new(),
+            next_plugin_id: 0,
+            results: Vec::new(),
+            errors: Vec::new(),
             current_focus: 0,
-            _config: Table::new(),
+            daemon: false,
+            window: None,
+            dmenu: false,
+            multi_select: false,
+            marked: Vec::new(),
+            prompt: CLI.prompt.clone().unwrap_or_else(|| DEFAULT_PROMPT.into()),
+            config: Table::new(),
+            window_config: WindowConfig::default(),
+            keybindings: Keybindings::new(),
+            unknown_actions: Vec::new(),
+            action_menu: None,
         }
     }
 }
 
+/// A single row of the result list, the row index is the position within the list
+#[derive(Debug, Clone)]
+struct ResultEntry {
+    plugin: usize,
+    entry: PluginEntry,
+}
+
+/// The actions offered for a single entry, the row index is the position within `actions`
+#[derive(Debug, Clone)]
+struct ActionMenu {
+    plugin: usize,
+    entry: PluginEntry,
+    actions: Vec<PluginEntry>,
+    focus: usize,
+}
+
+#[to_layer_message(multi)]
 #[derive(Debug, Clone)]
 enum Message {
     SetFilterText(String),
     Exit,
     LaunchEntry(usize),
     LaunchFocusedEntry,
+    MarkFocusedEntry,
+    FocusEntry(usize),
+    LaunchAction(usize),
+    FocusAction(usize),
     MoveApplicationFocus(FocusDirection),
-    PluginSubMsg(usize, PluginMsg),
+    PluginNotify(usize),
+    Daemon(DaemonCommand),
+    ConfigChanged,
+    ConfigWatchFailed(String),
+    KeyPressed(KeyChord),
     FocusSearch,
-}
-
-impl TryInto<iced_layershell::actions::LayerShellCustomActionWithId> for Message {
-    type Error = Self;
-    fn try_into(
-        self,
-    ) -> Result<iced_layershell::actions::LayerShellCustomActionWithId, Self::Error> {
-        Err(self)
-    }
-}
-
-type PluginMap = HashMap<usize, (PluginModel, PluginFuncs)>;
-
-fn get_plugins(config: &Table) -> (PluginMap, Vec<Task<Message>>) {
-    let mut plugins = HashMap::new();
-    let mut tasks = Vec::new();
-    // TODO make configurable
-    let plugin_dir = get_oxirun_dir().join("plugins");
-    if !plugin_dir.is_dir() {
-        std::fs::create_dir(&plugin_dir).expect("Could not create config dir");
-    }
-    let allowed_files = get_allowed_plugins(config);
-    for (index, res) in plugin_dir
-        .read_dir()
-        .expect("Could not read plugin directory")
-        .enumerate()
-    {
-        if let Ok(file) = res {
-            if allowed_files.contains(&file.file_name().to_str().unwrap_or("")) {
-                unsafe {
-                    let lib = Box::leak(Box::new(
-                        libloading::Library::new(file.path()).expect("Could not load library"),
-                    ));
-                    if let Some(plugin) = load_plugin(lib) {
-                        let (model, task_opt) = (plugin.model.clone())(config.clone());
-                        plugins.insert(index, (model, plugin));
-                        if let Some(task) = task_opt
-                            .map(|val| val.map(move |msg| Message::PluginSubMsg(index, msg)))
-                        {
-                            tasks.push(task)
-                        }
-                    }
-                }
-            }
-        }
-    }
-    (plugins, tasks)
+    WindowEvent(window::Id),
 }
 
 fn content_button(
-    focused_index: usize,
-    current_index: usize,
+    is_focused: bool,
+    height: f32,
     content: Element<Message>,
+    on_press: Message,
+    on_enter: Message,
 ) -> Element<Message> {
-    oxi_button::button(content, ButtonVariant::Primary)
-        .on_press(Message::LaunchEntry(current_index))
+    let button = oxi_button::button(content, ButtonVariant::Primary)
+        .on_press(on_press)
         .style(move |theme, status| {
-            let is_focused = current_index == focused_index;
             let palette = &OXITHEME;
             let default_style = oxi_button::primary_bg_button(theme, status);
             let background = if status == button::Status::Hovered {
@@ -175,48 +252,93 @@ fn content_button(
         })
         .padding(5.0)
         .width(Length::Fill)
-        .height(Length::Fixed(ICON_SIZE))
+        .height(Length::Fixed(height));
+    mouse_area(button).on_enter(on_enter).into()
+}
+
+fn entry_view<'a>(
+    entry: &PluginEntry,
+    marked: bool,
+    window_config: &WindowConfig,
+) -> Element<'a, Message> {
+    let icon = entry.icon.as_ref().and_then(|path| {
+        let widget: Element<Message> = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("svg") => svg(svg::Handle::from_path(path))
+                .height(Length::Fill)
+                .width(Length::Fixed(window_config.icon_size))
+                .into(),
+            Some("png") => image(path)
+                .height(Length::Fill)
+                .width(Length::Fixed(window_config.icon_size))
+                .into(),
+            _ => return None,
+        };
+        Some(widget)
+    });
+    let title = if marked {
+        format!("✓ {}", entry.title)
+    } else {
+        entry.title.clone()
+    };
+    let mut description = Column::new().push(text(title));
+    if let Some(subtitle) = &entry.subtitle {
+        description = description.push(text(subtitle.clone()).size(window_config.subtitle_size));
+    }
+    Row::new()
+        .push(icon)
+        .push(
+            container(description)
+                .center_y(Length::Fill)
+                .align_right(Length::Fill),
+        )
         .into()
 }
 
-fn plugin_launch(model: &mut OxiRun, focused_index: usize) -> Vec<Task<Message>> {
-    model
-        .plugins
-        .iter_mut()
-        .filter_map(|(index, (plugin_model, funcs))| {
-            let index = *index;
-            let launch_func = funcs.launch.clone();
-            let task_opt = unsafe { (launch_func)(focused_index, plugin_model.clone()) };
-            task_opt.map(move |task| task.map(move |msg| Message::PluginSubMsg(index, msg)))
-        })
-        .collect::<Vec<_>>()
+fn plugin_launch(model: &mut OxiRun, row: usize) {
+    if let Some(entry) = model.results.get(row) {
+        let (plugin_id, entry_id) = (entry.plugin, entry.entry.id);
+        plugin_launch_id(model, plugin_id, entry_id);
+    }
 }
 
-fn plugin_sort(model: &mut OxiRun, filter_text: String) -> Vec<Task<Message>> {
-    model
-        .plugins
-        .iter_mut()
-        .filter_map(|(index, (plugin_model, funcs))| {
-            let index = *index;
-            let sort_func = funcs.sort.clone();
-            let task_opt = unsafe { (sort_func)(filter_text.clone(), plugin_model.clone()) };
-            task_opt.map(move |task| task.map(move |msg| Message::PluginSubMsg(index, msg)))
-        })
-        .collect::<Vec<_>>()
+fn plugin_launch_id(model: &mut OxiRun, plugin_id: usize, entry_id: u64) {
+    let activation_token = model.launch_token();
+    if let Some(plugin) = model.plugins.get_mut(&plugin_id) {
+        plugin.launch(entry_id, activation_token.as_deref());
+    }
+}
+
+fn plugin_query(model: &mut OxiRun, filter_text: &str) {
+    for plugin in model.plugins.values_mut() {
+        if filter_text.is_empty() {
+            plugin.no_query();
+        } else {
+            plugin.query(filter_text);
+        }
+    }
 }
 
-fn plugin_count(model: &mut OxiRun) -> usize {
-    model
-        .plugins
-        .iter_mut()
-        .map(|(_, (model, funcs))| {
-            let count_func = funcs.count.clone();
-            unsafe { (count_func)(model.clone()) }
+/// Ranks the entries of all plugins together by their weighted score.
+/// Ties are broken by the plugin order and then by the order the plugin returned its entries in.
+fn plugin_results(model: &OxiRun) -> Vec<ResultEntry> {
+    let mut results = model
+        .plugin_order
+        .iter()
+        .filter_map(|(_, id)| Some((*id, model.plugins.get(id)?)))
+        .flat_map(|(id, plugin)| {
+            let ranking = model.rankings.get(&id).copied().unwrap_or_default();
+            plugin.entries().into_iter().map(move |mut entry| {
+                entry.score = ranking.apply(entry.score);
+                ResultEntry { plugin: id, entry }
+            })
         })
-        .sum::<usize>()
+        .collect::<Vec<_>>();
+    // sort_by_key is stable, which keeps the tie-break order from above
+    results.sort_by_key(|result| Reverse(result.entry.score));
+    results
 }
 
-fn error_view<'a>(plugin_name: &'static str, errors: Vec<String>) -> Option<Element<'a, Message>> {
+fn error_view<'a>(plugin_name: String, errors: Vec<String>) -> Option<Element<'a, Message>> {
     let mut col = Column::new();
     if errors.is_empty() {
         return None;
@@ -231,18 +353,253 @@ fn error_view<'a>(plugin_name: &'static str, errors: Vec<String>) -> Option<Elem
 
 impl OxiRun {
     fn new() -> (Self, Task<Message>) {
-        let (plugins, mut plugin_tasks) = get_plugins(&CONFIG);
-        plugin_tasks.push(focus("search_box"));
+        let mut model = Self {
+            config: config().to_owned(),
+            window_config: window_config().clone(),
+            errors: config_errors(),
+            ..Default::default()
+        };
+        let keybinding_errors = model.load_keybindings();
+        model.errors.extend(keybinding_errors);
+        let plugin_errors = model.load_plugins();
+        model.errors.extend(plugin_errors);
+        model.check_keybindings();
+        model.reset_query();
+        (model, focus("search_box"))
+    }
+
+    /// Offers the lines of stdin through the dmenu plugin instead of the configured plugins
+    fn new_dmenu() -> (Self, Task<Message>) {
+        let items = std::io::stdin()
+            .lock()
+            .lines()
+            .map_while(Result::ok)
+            .collect::<Vec<_>>();
+        let mut plugins: HashMap<usize, Box<dyn Plugin>> = HashMap::new();
+        plugins.insert(0, Box::new(DmenuPlugin::new(items, &CLI.dmenu)));
+        let mut model = Self {
+            config: config().to_owned(),
+            window_config: window_config().clone(),
+            plugins,
+            plugin_order: vec![(String::from("dmenu"), 0)],
+            next_plugin_id: 1,
+            errors: config_errors(),
+            dmenu: true,
+            multi_select: CLI.dmenu.multi_select,
+            ..Default::default()
+        };
+        let keybinding_errors = model.load_keybindings();
+        model.errors.extend(keybinding_errors);
+        model.reset_query();
+        (model, focus("search_box"))
+    }
+
+    fn new_daemon() -> (Self, Task<Message>) {
+        let (model, _) = Self::new();
         (
             Self {
-                _config: CONFIG.to_owned(),
-                plugins,
-                ..Default::default()
+                daemon: true,
+                ..model
             },
-            Task::batch(plugin_tasks),
+            Task::none(),
         )
     }
 
+    /// Loads the plugins of the `plugins` array, plugins which are already loaded are reconfigured
+    /// instead and plugins which are no longer listed are dropped.
+    /// Shared libraries are loaded into OxiRun, any other file is started as a plugin process.
+    fn load_plugins(&mut self) -> Vec<String> {
+        let mut errors = Vec::new();
+        // the loaded plugins are kept, nothing can be loaded without the dir
+        let plugin_dir = match get_plugin_dir(&CLI) {
+            Ok(plugin_dir) => plugin_dir,
+            Err(error) => {
+                errors.push(error.to_string());
+                return errors;
+            }
+        };
+        let serialized_config = toml::to_string(&self.config).unwrap_or_default();
+        let mut previous_order = std::mem::take(&mut self.plugin_order);
+        for plugin_file in get_allowed_plugins(&self.config) {
+            let previous_id = previous_order
+                .iter()
+                .position(|(file, _)| file == plugin_file)
+                .map(|position| previous_order.remove(position).1);
+            if let Some(id) = previous_id {
+                let reconfigured = self
+                    .plugins
+                    .get_mut(&id)
+                    .is_some_and(|plugin| plugin.reconfigure(&self.config));
+                if reconfigured {
+                    let (ranking, ranking_error) = get_plugin_ranking(&self.config, plugin_file);
+                    self.rankings.insert(id, ranking);
+                    errors.extend(ranking_error);
+                    self.plugin_order.push((plugin_file.to_string(), id));
+                    continue;
+                }
+                // the old instance has to be released before its library is loaded again
+                self.plugins.remove(&id);
+                self.rankings.remove(&id);
+            }
+
+            let plugin_path = plugin_dir.join(plugin_file);
+            if !plugin_path.is_file() {
+                continue;
+            }
+            // ids are never reused, notifications of dropped plugins are simply ignored
+            let id = self.next_plugin_id;
+            self.next_plugin_id += 1;
+            let plugin_res = if plugin_path.extension().is_some_and(|ext| ext == "so") {
+                load_plugin(plugin_path, id, &serialized_config, CLI.daemon)
+                    .map(|plugin| Box::new(plugin) as Box<dyn Plugin>)
+            } else {
+                spawn_plugin(&plugin_path, id, &self.config, CLI.daemon)
+                    .map(|plugin| Box::new(plugin) as Box<dyn Plugin>)
+            };
+            match plugin_res {
+                Ok(plugin) => {
+                    self.plugins.insert(id, plugin);
+                    let (ranking, ranking_error) = get_plugin_ranking(&self.config, plugin_file);
+                    self.rankings.insert(id, ranking);
+                    errors.extend(ranking_error);
+                    self.plugin_order.push((plugin_file.to_string(), id));
+                }
+                Err(error) => errors.push(format!("{plugin_file}: {error}")),
+            }
+        }
+        for (_, id) in previous_order {
+            self.plugins.remove(&id);
+            self.rankings.remove(&id);
+        }
+        errors
+    }
+
+    /// Reads the keybindings of the current config, returns the invalid ones
+    fn load_keybindings(&mut self) -> Vec<String> {
+        let (keybindings, errors) = get_keybindings(&self.config);
+        self.keybindings = keybindings;
+        errors
+    }
+
+    /// Requests an activation token for a single launch, dmenu mode only prints the entries.
+    /// Without a token the application is merely not focused, the error is only shown once.
+    fn launch_token(&mut self) -> Option<String> {
+        if self.dmenu {
+            return None;
+        }
+        match activation_token() {
+            Ok(token) => Some(token),
+            Err(error) => {
+                if !self.errors.contains(&error) {
+                    self.errors.push(error);
+                }
+                None
+            }
+        }
+    }
+
+    /// Checks the configured keybindings against the actions of the loaded plugins.
+    /// Dmenu mode only offers the built in actions, bindings of plugin actions are left alone.
+    fn check_keybindings(&mut self) {
+        if self.dmenu {
+            return;
+        }
+        let plugin_actions = self
+            .plugins
+            .values()
+            .flat_map(|plugin| plugin.actions())
+            .collect::<Vec<_>>();
+        self.unknown_actions = unknown_actions(&self.config, &plugin_actions);
+    }
+
+    /// Applies a changed config file to the plugins and the window.
+    /// The keyboard mode only applies to windows shown afterwards.
+    fn reload_config(&mut self) -> Task<Message> {
+        let (config, config_error) = get_config(&CLI);
+        let (window_config, window_errors) = get_window_config(&config, &CLI);
+        self.config = config;
+        self.window_config = window_config;
+        self.errors = config_error
+            .iter()
+            .map(ToString::to_string)
+            .chain(window_errors)
+            .collect();
+        let keybinding_errors = self.load_keybindings();
+        self.errors.extend(keybinding_errors);
+        // the stdin entries of dmenu mode do not depend on the config
+        if !self.dmenu {
+            let plugin_errors = self.load_plugins();
+            self.errors.extend(plugin_errors);
+        }
+        self.check_keybindings();
+        let filter_text = self.filter_text.clone();
+        plugin_query(self, &filter_text);
+        self.refresh_results();
+
+        let Some(id) = self.window else {
+            return Task::none();
+        };
+        Task::batch([
+            Task::done(Message::AnchorSizeChange {
+                id,
+                anchor: self.window_config.anchor(),
+                size: self.window_config.size(),
+            }),
+            Task::done(Message::MarginChange {
+                id,
+                margin: self.window_config.margins(),
+            }),
+            Task::done(Message::LayerChange {
+                id,
+                layer: self.window_config.layer.into(),
+            }),
+        ])
+    }
+
+    fn window_settings(&self) -> NewLayerShellSettings {
+        NewLayerShellSettings {
+            size: Some(self.window_config.size()),
+            exclusive_zone: Some(0),
+            anchor: self.window_config.anchor(),
+            layer: self.window_config.layer.into(),
+            margin: Some(self.window_config.margins()),
+            keyboard_interactivity: self.window_config.keyboard_mode.into(),
+            ..Default::default()
+        }
+    }
+
+    fn show(&mut self) -> Task<Message> {
+        if self.window.is_some() {
+            return Task::none();
+        }
+        let id = window::Id::unique();
+        self.window = Some(id);
+        Task::done(Message::NewLayerShell {
+            settings: self.window_settings(),
+            id,
+        })
+        .chain(focus("search_box"))
+    }
+
+    /// Removes the window and resets the query, the plugins stay loaded for the next show
+    fn hide(&mut self) -> Task<Message> {
+        let Some(id) = self.window.take() else {
+            return Task::none();
+        };
+        self.current_focus = 0;
+        self.action_menu = None;
+        self.reset_query();
+        Task::done(Message::RemoveWindow(id))
+    }
+
+    /// Replaces the query with the one passed on the command line, or clears it
+    fn reset_query(&mut self) {
+        let query = CLI.query.clone().unwrap_or_default();
+        plugin_query(self, &query);
+        self.filter_text = query;
+        self.refresh_results();
+    }
+
     fn namespace() -> String {
         String::from("OxiRun")
     }
@@ -250,92 +607,355 @@ impl OxiRun {
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::SetFilterText(value) => {
-                self.filter_text = value.clone();
-                Task::batch(plugin_sort(self, value))
+                self.action_menu = None;
+                plugin_query(self, &value);
+                self.filter_text = value;
+                self.refresh_results();
+                Task::none()
             }
+            Message::Exit if self.daemon => self.hide(),
+            // dmenu scripts rely on the exit code to detect a cancelled selection
+            Message::Exit if self.dmenu => std::process::exit(1),
             Message::Exit => std::process::exit(0),
-            Message::LaunchEntry(focused_index) => {
-                let tasks = plugin_launch(self, focused_index);
-                Task::batch(tasks).chain(Task::done(Message::Exit))
-            }
+            Message::LaunchEntry(row) => self.launch(row),
             Message::MoveApplicationFocus(direction) => {
-                self.current_focus = direction.add(self.current_focus, plugin_count(self));
+                self.current_focus = direction.add(self.current_focus, self.results.len());
+                Task::none()
+            }
+            Message::LaunchFocusedEntry => self.launch(self.current_focus),
+            Message::MarkFocusedEntry => {
+                if self.multi_select
+                    && let Some(result) = self.results.get(self.current_focus)
+                {
+                    let key = (result.plugin, result.entry.id);
+                    if let Some(position) = self.marked.iter().position(|marked| *marked == key) {
+                        self.marked.remove(position);
+                    } else {
+                        self.marked.push(key);
+                    }
+                }
                 Task::none()
             }
-            Message::LaunchFocusedEntry => {
-                let tasks = plugin_launch(self, self.current_focus);
-                Task::batch(tasks).chain(Task::done(Message::Exit))
+            Message::FocusEntry(row) => {
+                self.current_focus = row;
+                Task::none()
+            }
+            Message::LaunchAction(row) => self.launch_action(row),
+            Message::FocusAction(row) => {
+                if let Some(menu) = &mut self.action_menu {
+                    menu.focus = row;
+                }
+                Task::none()
             }
-            Message::PluginSubMsg(index, msg) => unsafe {
-                let plugin = self.plugins.get_mut(&index).unwrap();
-                let update_func = plugin.1.update.clone();
-                let task_opt = (update_func)(self.filter_text.clone(), plugin.0.clone(), msg);
-                if let Some(task) = task_opt {
-                    task.map(move |msg| Message::PluginSubMsg(index, msg))
+            Message::PluginNotify(id) => {
+                if let Some(plugin) = self.plugins.get_mut(&id) {
+                    plugin.update();
+                }
+                self.check_keybindings();
+                self.refresh_results();
+                Task::none()
+            }
+            Message::Daemon(DaemonCommand::Show) => self.show(),
+            Message::Daemon(DaemonCommand::Hide) => self.hide(),
+            Message::Daemon(DaemonCommand::Toggle) => {
+                if self.window.is_some() {
+                    self.hide()
                 } else {
-                    Task::none()
+                    self.show()
                 }
+            }
+            Message::ConfigChanged => self.reload_config(),
+            Message::ConfigWatchFailed(error) => {
+                self.errors.push(error);
+                Task::none()
+            }
+            Message::KeyPressed(chord) => match self.keybindings.get(&chord).cloned() {
+                Some(action) => self.run_action(action),
+                // unbound keys are meant for the search box
+                None => focus("search_box"),
             },
             Message::FocusSearch => focus("search_box"),
+            Message::WindowEvent(id) => {
+                if !self.daemon && self.window.is_none() {
+                    self.window = Some(id);
+                }
+                focus("search_box")
+            }
+            _ => Task::none(),
         }
     }
 
-    fn view(&self) -> Element<Message> {
-        let plugin_views = self
-            .plugins
-            .iter()
-            .flat_map(|(index, (model, funcs))| {
-                let view_func = funcs.view.clone();
-                let view_res = unsafe { (view_func)(model.clone()) };
-                match view_res {
-                    Ok(view) => {
-                        let mut combined = view
-                            .into_iter()
-                            .map(move |(score, element)| {
-                                (
-                                    score,
-                                    element.map(|msg| Message::PluginSubMsg(*index, msg.clone())),
-                                )
-                            })
-                            .collect::<Vec<_>>();
-                        // TODO do we NEED to sort again? it shouldn't sort more than a bit more
-                        // than max entries -> all matching plugin entries but still annoying
-                        combined.sort_by(|first, second| second.0.cmp(&first.0));
-                        combined.into_iter().map(|val| val.1).collect::<Vec<_>>()
+    fn run_action(&mut self, action: Action) -> Task<Message> {
+        if self.action_menu.is_some() {
+            return self.run_menu_action(action);
+        }
+        let last_row = self.results.len().saturating_sub(1);
+        match action {
+            Action::Next => self.update(Message::MoveApplicationFocus(FocusDirection::Down)),
+            Action::Prev => self.update(Message::MoveApplicationFocus(FocusDirection::Up)),
+            Action::PageDown => {
+                self.current_focus = (self.current_focus + self.page_size()).min(last_row);
+                Task::none()
+            }
+            Action::PageUp => {
+                self.current_focus = self.current_focus.saturating_sub(self.page_size());
+                Task::none()
+            }
+            Action::First => {
+                self.current_focus = 0;
+                Task::none()
+            }
+            Action::Last => {
+                self.current_focus = last_row;
+                Task::none()
+            }
+            Action::Launch => self.update(Message::LaunchFocusedEntry),
+            Action::Secondary => {
+                let first_action = self.focused_entry_actions().first().map(|action| action.id);
+                match first_action {
+                    Some(action_id) => {
+                        self.open_action_menu();
+                        self.launch_action_id(action_id)
+                    }
+                    None => {
+                        self.run_plugin_action(None);
+                        Task::none()
                     }
-                    // TODO use error
-                    Err(_) => Vec::new(),
                 }
+            }
+            Action::Actions => {
+                self.open_action_menu();
+                Task::none()
+            }
+            Action::Back => Task::none(),
+            Action::Mark => self.update(Message::MarkFocusedEntry),
+            Action::Clear => self
+                .update(Message::SetFilterText(String::new()))
+                .chain(focus("search_box")),
+            Action::Exit => self.update(Message::Exit),
+            Action::Plugin(action) => {
+                self.run_plugin_action(Some(&action));
+                Task::none()
+            }
+        }
+    }
+
+    /// Keys act on the action menu while it is open, leaving it with exit or back
+    fn run_menu_action(&mut self, action: Action) -> Task<Message> {
+        let page_size = self.page_size();
+        let Some(menu) = &mut self.action_menu else {
+            return Task::none();
+        };
+        let length = menu.actions.len();
+        let last_row = length.saturating_sub(1);
+        match action {
+            Action::Next => menu.focus = FocusDirection::Down.add(menu.focus, length),
+            Action::Prev => menu.focus = FocusDirection::Up.add(menu.focus, length),
+            Action::PageDown => menu.focus = (menu.focus + page_size).min(last_row),
+            Action::PageUp => menu.focus = menu.focus.saturating_sub(page_size),
+            Action::First => menu.focus = 0,
+            Action::Last => menu.focus = last_row,
+            Action::Launch | Action::Actions => {
+                let row = menu.focus;
+                return self.launch_action(row);
+            }
+            Action::Exit | Action::Back => self.action_menu = None,
+            Action::Secondary | Action::Mark | Action::Clear | Action::Plugin(_) => (),
+        }
+        Task::none()
+    }
+
+    fn focused_entry_actions(&self) -> Vec<PluginEntry> {
+        self.results
+            .get(self.current_focus)
+            .and_then(|result| {
+                let plugin = self.plugins.get(&result.plugin)?;
+                Some(plugin.entry_actions(result.entry.id))
             })
-            .enumerate()
-            .map(|(elem_index, val)| content_button(self.current_focus, elem_index, val))
-            .collect::<Vec<_>>();
+            .unwrap_or_default()
+    }
+
+    /// Opens the action menu of the focused entry, entries without actions have no menu
+    fn open_action_menu(&mut self) {
+        let Some(result) = self.results.get(self.current_focus) else {
+            return;
+        };
+        let actions = self.focused_entry_actions();
+        if actions.is_empty() {
+            return;
+        }
+        self.action_menu = Some(ActionMenu {
+            plugin: result.plugin,
+            entry: result.entry.clone(),
+            actions,
+            focus: 0,
+        });
+    }
+
+    fn launch_action(&mut self, row: usize) -> Task<Message> {
+        let action_id = self
+            .action_menu
+            .as_ref()
+            .and_then(|menu| menu.actions.get(row))
+            .map(|action| action.id);
+        match action_id {
+            Some(action_id) => self.launch_action_id(action_id),
+            None => Task::none(),
+        }
+    }
+
+    /// Launches an action of the entry the open action menu belongs to and closes the menu.
+    /// OxiRun only closes when something was launched, other actions like pinning the entry
+    /// change the results instead.
+    fn launch_action_id(&mut self, action_id: u64) -> Task<Message> {
+        let Some(menu) = self.action_menu.take() else {
+            return Task::none();
+        };
+        let activation_token = self.launch_token();
+        let launched = self.plugins.get_mut(&menu.plugin).is_some_and(|plugin| {
+            plugin.launch_action(menu.entry.id, action_id, activation_token.as_deref())
+        });
+        if launched {
+            return Task::done(Message::Exit);
+        }
+        let filter_text = self.filter_text.clone();
+        plugin_query(self, &filter_text);
+        self.refresh_results();
+        Task::none()
+    }
+
+    /// Runs an action of the plugin owning the focused entry on that entry.
+    /// Without a name the first action the plugin offers is used.
+    fn run_plugin_action(&mut self, action: Option<&str>) {
+        let Some(result) = self.results.get(self.current_focus) else {
+            return;
+        };
+        let entry_id = result.entry.id;
+        let Some(plugin) = self.plugins.get_mut(&result.plugin) else {
+            return;
+        };
+        let actions = plugin.actions();
+        let action = match action {
+            Some(action) => actions.iter().find(|candidate| *candidate == action),
+            None => actions.first(),
+        };
+        if let Some(action) = action {
+            plugin.run_action(action, entry_id);
+        }
+        self.refresh_results();
+    }
+
+    /// Amount of rows fitting into the window below the search box, every row is followed by the
+    /// spacing of the column
+    fn page_size(&self) -> usize {
+        let available = self.window_config.height as f32 - SEARCH_BOX_HEIGHT - MEDIUM_SPACING;
+        let row_height = self.window_config.icon_size + MEDIUM_SPACING;
+        (available / row_height).max(1.0) as usize
+    }
+
+    fn launch(&mut self, row: usize) -> Task<Message> {
+        if !self.dmenu {
+            plugin_launch(self, row);
+            return Task::done(Message::Exit);
+        }
+        if !self.marked.is_empty() {
+            for (plugin_id, entry_id) in std::mem::take(&mut self.marked) {
+                plugin_launch_id(self, plugin_id, entry_id);
+            }
+        } else if row < self.results.len() {
+            plugin_launch(self, row);
+        } else {
+            // like dmenu, accepting without a match prints the entered text
+            println!("{}", self.filter_text);
+        }
+        std::process::exit(0)
+    }
+
+    fn refresh_results(&mut self) {
+        self.results = plugin_results(self);
+        if self.current_focus >= self.results.len() {
+            self.current_focus = 0;
+        }
+    }
+
+    fn view(&self) -> Element<Message> {
+        let plugin_views = match &self.action_menu {
+            Some(menu) => self.action_menu_view(menu),
+            // scrolls by whole pages, the focused row is always shown
+            None => self
+                .results
+                .iter()
+                .enumerate()
+                .skip(self.current_focus - self.current_focus % self.page_size())
+                .take(self.page_size())
+                .map(|(row, result)| {
+                    let marked = self.marked.contains(&(result.plugin, result.entry.id));
+                    content_button(
+                        row == self.current_focus,
+                        self.window_config.icon_size,
+                        entry_view(&result.entry, marked, &self.window_config),
+                        Message::LaunchEntry(row),
+                        Message::FocusEntry(row),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        };
 
         let mut col = Column::new();
         col = col.push(
-            text_input(
-                "Enter text to find",
-                self.filter_text.as_str(),
-                Message::SetFilterText,
+            container(
+                text_input(
+                    self.prompt.as_str(),
+                    self.filter_text.as_str(),
+                    Message::SetFilterText,
+                )
+                .id("search_box"),
             )
-            .id("search_box"),
+            .height(Length::Fixed(SEARCH_BOX_HEIGHT)),
         );
         for entry in plugin_views {
             col = col.push(entry);
         }
 
-        let mut plugin_error_views = Row::new();
-        for (_, plugin) in self.plugins.iter() {
-            unsafe {
-                plugin_error_views = plugin_error_views.push(error_view(
-                    (plugin.1.name)(),
-                    (plugin.1.errors)(plugin.0.clone()).clone(),
-                ))
-            }
+        let errors = self
+            .errors
+            .iter()
+            .chain(&self.unknown_actions)
+            .cloned()
+            .collect();
+        let mut plugin_error_views = Row::new().push(error_view(String::from("OxiRun"), errors));
+        for plugin in self.plugins.values() {
+            plugin_error_views =
+                plugin_error_views.push(error_view(plugin.name(), plugin.errors()));
         }
         col = col.push(plugin_error_views);
 
-        rounded_layer(col.width(Length::Fill).spacing(MEDIUM_SPACING), WINDOW_SIZE)
+        rounded_layer(
+            col.width(Length::Fill).spacing(MEDIUM_SPACING),
+            self.window_config.size(),
+        )
+    }
+
+    /// The entry the menu belongs to followed by its actions
+    fn action_menu_view(&self, menu: &ActionMenu) -> Vec<Element<Message>> {
+        let header = container(entry_view(&menu.entry, false, &self.window_config))
+            .padding(5.0)
+            .height(Length::Fixed(self.window_config.icon_size))
+            .into();
+        let actions = menu.actions.iter().enumerate().map(|(row, action)| {
+            content_button(
+                row == menu.focus,
+                self.window_config.icon_size,
+                entry_view(action, false, &self.window_config),
+                Message::LaunchAction(row),
+                Message::FocusAction(row),
+            )
+        });
+        std::iter::once(header).chain(actions).collect()
+    }
+
+    fn view_window(&self, _id: window::Id) -> Element<Message> {
+        self.view()
     }
 
     fn theme(&self) -> Theme {
@@ -343,30 +963,26 @@ impl OxiRun {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        event::listen_with(move |event, _status, _id| match event {
-            iced::Event::Keyboard(iced::keyboard::Event::KeyPressed {
-                modifiers: modifier,
-                key: iced::keyboard::key::Key::Named(key),
-                modified_key: _,
-                physical_key: _,
-                location: _,
-                text: _,
-                repeat: _,
-            }) => match key {
-                Named::Escape => Some(Message::Exit),
-                Named::Enter => Some(Message::LaunchFocusedEntry),
-                Named::ArrowUp => Some(Message::MoveApplicationFocus(FocusDirection::Up)),
-                Named::Tab if modifier == Modifiers::SHIFT => {
-                    Some(Message::MoveApplicationFocus(FocusDirection::Up))
-                }
-                Named::ArrowDown => Some(Message::MoveApplicationFocus(FocusDirection::Down)),
-                Named::Tab if modifier == Modifiers::empty() => {
-                    Some(Message::MoveApplicationFocus(FocusDirection::Down))
-                }
-                _ => None,
-            },
+        // the chords are looked up in update, as the keybindings can change at runtime
+        let keyboard = event::listen_with(move |event, _status, id| match event {
+            iced::Event::Keyboard(iced::keyboard::Event::KeyPressed { key, modifiers, .. }) => {
+                Some(match KeyChord::from_key(&key, modifiers) {
+                    Some(chord) => Message::KeyPressed(chord),
+                    None => Message::FocusSearch,
+                })
+            }
+            iced::Event::Window(_) => Some(Message::WindowEvent(id)),
             _ => Some(Message::FocusSearch),
-        })
+        });
+        Subscription::batch([
+            keyboard,
+            Subscription::run(plugin_notifications).map(Message::PluginNotify),
+            Subscription::run(daemon_commands).map(Message::Daemon),
+            Subscription::run(config_changes).map(|change| match change {
+                Ok(()) => Message::ConfigChanged,
+                Err(error) => Message::ConfigWatchFailed(error),
+            }),
+        ])
     }
 
     // remove the annoying background color
@@ -375,6 +991,6 @@ impl OxiRun {
     }
 
     fn scale_factor(&self) -> f32 {
-        SCALE_FACTOR
+        self.window_config.scale
     }
 }
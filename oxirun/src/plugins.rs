@@ -1,132 +1,265 @@
-use std::sync::{Arc, RwLock};
+use std::{ffi::c_void, fmt::Display, path::PathBuf, sync::Mutex};
 
-use iced::{Element, Task};
+use iced::futures::{
+    Stream, StreamExt,
+    channel::mpsc::{UnboundedReceiver, UnboundedSender, unbounded},
+    stream,
+};
 use libloading::Library;
-use oxiced::any_send::OxiAny;
+use once_cell::sync::Lazy;
+use oxirun_plugin::abi::{
+    ABI_VERSION, ABI_VERSION_SYMBOL, FfiStr, HostCallbacks, PluginState, PluginVTable,
+    VTABLE_SYMBOL,
+};
 use toml::Table;
 
-pub type PluginModel = Arc<RwLock<&'static mut dyn OxiAny>>;
-pub type PluginMsg = Arc<&'static mut dyn OxiAny>;
-
-#[allow(improper_ctypes_definitions)]
-#[derive(Clone, Debug)]
-pub struct PluginFuncs {
-    pub model: libloading::Symbol<
-        'static,
-        unsafe extern "C" fn(Table) -> (PluginModel, Option<Task<PluginMsg>>),
-    >,
-    pub update: libloading::Symbol<
-        'static,
-        unsafe extern "C" fn(
-            filter_text: String,
-            model: PluginModel,
-            msg: PluginMsg,
-        ) -> Option<Task<PluginMsg>>,
-    >,
-    pub sort: libloading::Symbol<
-        'static,
-        unsafe extern "C" fn(filter_text: String, model: PluginModel) -> Option<Task<PluginMsg>>,
-    >,
-    pub launch: libloading::Symbol<
-        'static,
-        unsafe extern "C" fn(focused_index: usize, model: PluginModel) -> Option<Task<PluginMsg>>,
-    >,
-    /// The i64 represents the score of each element, this can also be used to ensure your plugin is at
-    /// the top or close to the top
-    pub view: libloading::Symbol<
-        'static,
-        unsafe extern "C" fn(
-            model: PluginModel,
-        )
-            -> Result<Vec<(i64, Element<'static, PluginMsg>)>, std::io::Error>,
-    >,
-    pub errors:
-        libloading::Symbol<'static, unsafe extern "C" fn(model: PluginModel) -> Vec<String>>,
-    pub name: libloading::Symbol<'static, unsafe extern "C" fn() -> &'static str>,
-    pub count: libloading::Symbol<'static, unsafe extern "C" fn(model: PluginModel) -> usize>,
+pub use dmenu::DmenuPlugin;
+pub use process::spawn_plugin;
+
+mod dmenu;
+mod process;
+
+type Notifications = (
+    UnboundedSender<usize>,
+    Mutex<Option<UnboundedReceiver<usize>>>,
+);
+
+/// Plugins notify the host from arbitrary threads, the receiving end is turned into a
+/// subscription by [`plugin_notifications`].
+static NOTIFICATIONS: Lazy<Notifications> = Lazy::new(|| {
+    let (sender, receiver) = unbounded();
+    (sender, Mutex::new(Some(receiver)))
+});
+
+/// Wakes up the host, which will then call [`Plugin::update`] on the plugin with this index.
+fn notify(index: usize) {
+    let _ = NOTIFICATIONS.0.unbounded_send(index);
 }
 
-pub fn load_plugin(lib: &'static Library) -> Option<PluginFuncs> {
+/// The context handed to a plugin is its index, no memory is shared which could outlive the host.
+extern "C" fn notify_host(context: *mut c_void) {
+    notify(context as usize);
+}
+
+/// Yields the index of every plugin that called notify.
+pub fn plugin_notifications() -> impl Stream<Item = usize> {
+    let receiver = NOTIFICATIONS
+        .1
+        .lock()
+        .ok()
+        .and_then(|mut receiver| receiver.take());
+    stream::iter(receiver).flatten()
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginEntry {
+    pub id: u64,
+    pub score: i64,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub icon: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub enum PluginLoadError {
+    Library(libloading::Error),
+    MissingSymbol(libloading::Error),
+    AbiMismatch { found: u32 },
+    Spawn(std::io::Error),
+}
+
+impl Display for PluginLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PluginLoadError::Library(error) => write!(f, "Could not load library: {error}"),
+            PluginLoadError::MissingSymbol(error) => {
+                write!(f, "Library is not an OxiRun plugin: {error}")
+            }
+            PluginLoadError::AbiMismatch { found } => write!(
+                f,
+                "Plugin was built for ABI version {found}, but OxiRun requires version {ABI_VERSION}"
+            ),
+            PluginLoadError::Spawn(error) => write!(f, "Could not start plugin process: {error}"),
+        }
+    }
+}
+
+/// A loaded plugin, all calls into the plugin go through this.
+pub trait Plugin {
+    fn name(&self) -> String;
+    /// Handles pending work after the plugin sent a notification
+    fn update(&mut self);
+    fn query(&mut self, filter_text: &str);
+    /// Called instead of `query` when the filter text is empty
+    fn no_query(&mut self);
+    /// `activation_token` was issued by the compositor for this launch
+    fn launch(&mut self, entry_id: u64, activation_token: Option<&str>);
+    fn entries(&self) -> Vec<PluginEntry>;
+    fn errors(&self) -> Vec<String>;
+    /// Applies a changed config, returns false if the plugin has to be loaded again instead
+    fn reconfigure(&mut self, config: &Table) -> bool;
+    /// Names of the actions users can bind to keys
+    fn actions(&self) -> Vec<String>;
+    fn run_action(&mut self, action: &str, entry_id: u64);
+    /// Actions offered for the entry, shown like entries in its action menu without a score
+    fn entry_actions(&self, entry_id: u64) -> Vec<PluginEntry>;
+    /// Returns whether something was launched, OxiRun stays open otherwise
+    fn launch_action(
+        &mut self,
+        entry_id: u64,
+        action_id: u64,
+        activation_token: Option<&str>,
+    ) -> bool;
+}
+
+/// A plugin loaded from a shared library.
+/// The state is released before the library is unloaded, see [`PluginVTable`] for the ownership.
+pub struct LibraryPlugin {
+    // only valid while the library is loaded
+    vtable: &'static PluginVTable,
+    state: PluginState,
+    _library: Library,
+}
+
+impl Drop for LibraryPlugin {
+    fn drop(&mut self) {
+        unsafe { (self.vtable.free)(self.state) }
+    }
+}
+
+impl Plugin for LibraryPlugin {
+    fn name(&self) -> String {
+        unsafe { (self.vtable.name)().as_str() }.to_string()
+    }
+
+    fn update(&mut self) {
+        unsafe { (self.vtable.update)(self.state) }
+    }
+
+    fn query(&mut self, filter_text: &str) {
+        unsafe { (self.vtable.query)(self.state, FfiStr::new(filter_text)) }
+    }
+
+    fn no_query(&mut self) {
+        unsafe { (self.vtable.no_query)(self.state) }
+    }
+
+    fn launch(&mut self, entry_id: u64, activation_token: Option<&str>) {
+        let activation_token = FfiStr::new(activation_token.unwrap_or_default());
+        unsafe { (self.vtable.launch)(self.state, entry_id, activation_token) }
+    }
+
+    fn entries(&self) -> Vec<PluginEntry> {
+        let entries = unsafe { (self.vtable.entries)(self.state).as_slice() };
+        entries
+            .iter()
+            .map(|entry| {
+                let (title, subtitle, icon) = unsafe {
+                    (
+                        entry.title.as_str(),
+                        entry.subtitle.as_str(),
+                        entry.icon.as_str(),
+                    )
+                };
+                PluginEntry {
+                    id: entry.id,
+                    score: entry.score,
+                    title: title.to_string(),
+                    subtitle: (!subtitle.is_empty()).then(|| subtitle.to_string()),
+                    icon: (!icon.is_empty()).then(|| PathBuf::from(icon)),
+                }
+            })
+            .collect()
+    }
+
+    fn errors(&self) -> Vec<String> {
+        let errors = unsafe { (self.vtable.errors)(self.state).as_slice() };
+        errors
+            .iter()
+            .map(|error| unsafe { error.as_str() }.to_string())
+            .collect()
+    }
+
+    fn reconfigure(&mut self, config: &Table) -> bool {
+        let Some(reconfigure) = self.vtable.reconfigure else {
+            return false;
+        };
+        let config = toml::to_string(config).unwrap_or_default();
+        unsafe { reconfigure(self.state, FfiStr::new(&config)) }
+    }
+
+    fn actions(&self) -> Vec<String> {
+        let actions = unsafe { (self.vtable.actions)(self.state).as_slice() };
+        actions
+            .iter()
+            .map(|action| unsafe { action.as_str() }.to_string())
+            .collect()
+    }
+
+    fn run_action(&mut self, action: &str, entry_id: u64) {
+        unsafe { (self.vtable.run_action)(self.state, FfiStr::new(action), entry_id) }
+    }
+
+    fn entry_actions(&self, entry_id: u64) -> Vec<PluginEntry> {
+        let actions = unsafe { (self.vtable.entry_actions)(self.state, entry_id).as_slice() };
+        actions
+            .iter()
+            .map(|action| {
+                let (title, icon) = unsafe { (action.title.as_str(), action.icon.as_str()) };
+                PluginEntry {
+                    id: action.id,
+                    score: 0,
+                    title: title.to_string(),
+                    subtitle: None,
+                    icon: (!icon.is_empty()).then(|| PathBuf::from(icon)),
+                }
+            })
+            .collect()
+    }
+
+    fn launch_action(
+        &mut self,
+        entry_id: u64,
+        action_id: u64,
+        activation_token: Option<&str>,
+    ) -> bool {
+        let activation_token = FfiStr::new(activation_token.unwrap_or_default());
+        unsafe { (self.vtable.launch_action)(self.state, entry_id, action_id, activation_token) }
+    }
+}
+
+/// Loads the plugin at the given path, `index` is used to route notifications of the plugin.
+/// Plugins built against a different ABI version are rejected before any other symbol is used.
+pub fn load_plugin(
+    path: PathBuf,
+    index: usize,
+    config: &str,
+    daemon: bool,
+) -> Result<LibraryPlugin, PluginLoadError> {
     unsafe {
-        let model: Result<
-            libloading::Symbol<
-                unsafe extern "C" fn(Table) -> (PluginModel, Option<Task<PluginMsg>>),
-            >,
-            libloading::Error,
-        > = lib.get(b"model");
-        let update: Result<
-            libloading::Symbol<
-                unsafe extern "C" fn(
-                    filter_text: String,
-                    model: Arc<RwLock<&mut dyn OxiAny>>,
-                    msg: PluginMsg,
-                ) -> Option<Task<PluginMsg>>,
-            >,
-            libloading::Error,
-        > = lib.get(b"update");
-        let sort: Result<
-            libloading::Symbol<
-                unsafe extern "C" fn(
-                    filter_text: String,
-                    model: PluginModel,
-                ) -> Option<Task<PluginMsg>>,
-            >,
-            libloading::Error,
-        > = lib.get(b"sort");
-        let launch: Result<
-            libloading::Symbol<
-                unsafe extern "C" fn(
-                    focused_index: usize,
-                    model: PluginModel,
-                ) -> Option<Task<PluginMsg>>,
-            >,
-            libloading::Error,
-        > = lib.get(b"launch");
-        let view: Result<
-            libloading::Symbol<
-                unsafe extern "C" fn(
-                    model: PluginModel,
-                ) -> Result<
-                    Vec<(i64, Element<'static, PluginMsg>)>,
-                    std::io::Error,
-                >,
-            >,
-            libloading::Error,
-        > = lib.get(b"view");
-        let errors: Result<
-            libloading::Symbol<unsafe extern "C" fn(model: PluginModel) -> Vec<String>>,
-            libloading::Error,
-        > = lib.get(b"errors");
-        let name: Result<
-            libloading::Symbol<unsafe extern "C" fn() -> &'static str>,
-            libloading::Error,
-        > = lib.get(b"name");
-        let count: Result<
-            libloading::Symbol<unsafe extern "C" fn(model: PluginModel) -> usize>,
-            libloading::Error,
-        > = lib.get(b"count");
-
-        match (model, update, sort, launch, view, errors, name, count) {
-            (
-                Ok(model),
-                Ok(update),
-                Ok(sort),
-                Ok(launch),
-                Ok(view),
-                Ok(errors),
-                Ok(name),
-                Ok(count),
-            ) => Some(PluginFuncs {
-                model,
-                update,
-                view,
-                sort,
-                launch,
-                errors,
-                name,
-                count,
-            }),
-            _ => None,
+        let lib = Library::new(path).map_err(PluginLoadError::Library)?;
+        let abi_version: libloading::Symbol<extern "C" fn() -> u32> =
+            lib.get(ABI_VERSION_SYMBOL)
+                .map_err(PluginLoadError::MissingSymbol)?;
+        let found = abi_version();
+        if found != ABI_VERSION {
+            return Err(PluginLoadError::AbiMismatch { found });
         }
+        let vtable: libloading::Symbol<extern "C" fn() -> &'static PluginVTable> = lib
+            .get(VTABLE_SYMBOL)
+            .map_err(PluginLoadError::MissingSymbol)?;
+        let vtable = vtable();
+
+        let host = HostCallbacks {
+            context: index as *mut c_void,
+            notify: notify_host,
+            daemon,
+        };
+        let state = (vtable.model)(FfiStr::new(config), host);
+        Ok(LibraryPlugin {
+            vtable,
+            state,
+            _library: lib,
+        })
     }
 }
@@ -0,0 +1,115 @@
+use std::cmp::Reverse;
+
+use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
+use toml::Table;
+
+use crate::cli::DmenuArgs;
+
+use super::{Plugin, PluginEntry};
+
+/// Offers the lines read from stdin, the selected line is printed to stdout.
+pub struct DmenuPlugin {
+    items: Vec<String>,
+    fuzzy_matcher: SkimMatcherV2,
+    lines: Option<usize>,
+    print_index: bool,
+    entries: Vec<PluginEntry>,
+}
+
+impl DmenuPlugin {
+    pub fn new(items: Vec<String>, args: &DmenuArgs) -> Self {
+        let fuzzy_matcher = if args.insensitive {
+            SkimMatcherV2::default().ignore_case()
+        } else {
+            SkimMatcherV2::default().respect_case()
+        };
+        let mut plugin = Self {
+            items,
+            fuzzy_matcher,
+            lines: args.lines,
+            print_index: args.index,
+            entries: Vec::new(),
+        };
+        plugin.query("");
+        plugin
+    }
+}
+
+impl Plugin for DmenuPlugin {
+    fn name(&self) -> String {
+        String::from("dmenu")
+    }
+
+    fn update(&mut self) {}
+
+    fn query(&mut self, filter_text: &str) {
+        let mut entries = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                // an empty query shows all items in their original order
+                let score = if filter_text.is_empty() {
+                    0
+                } else {
+                    self.fuzzy_matcher.fuzzy_match(item, filter_text)?
+                };
+                Some(PluginEntry {
+                    id: index as u64,
+                    score,
+                    title: item.clone(),
+                    subtitle: None,
+                    icon: None,
+                })
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| Reverse(entry.score));
+        if let Some(lines) = self.lines {
+            entries.truncate(lines);
+        }
+        self.entries = entries;
+    }
+
+    fn no_query(&mut self) {
+        self.query("");
+    }
+
+    fn launch(&mut self, entry_id: u64, _activation_token: Option<&str>) {
+        if self.print_index {
+            println!("{entry_id}");
+        } else if let Some(item) = self.items.get(entry_id as usize) {
+            println!("{item}");
+        }
+    }
+
+    fn entries(&self) -> Vec<PluginEntry> {
+        self.entries.clone()
+    }
+
+    fn errors(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn reconfigure(&mut self, _config: &Table) -> bool {
+        true
+    }
+
+    fn actions(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn run_action(&mut self, _action: &str, _entry_id: u64) {}
+
+    fn entry_actions(&self, _entry_id: u64) -> Vec<PluginEntry> {
+        Vec::new()
+    }
+
+    fn launch_action(
+        &mut self,
+        _entry_id: u64,
+        _action_id: u64,
+        _activation_token: Option<&str>,
+    ) -> bool {
+        false
+    }
+}
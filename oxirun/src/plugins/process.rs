@@ -0,0 +1,402 @@
+//! Plugins running as child processes.
+//!
+//! Host and plugin exchange [JSON-RPC 2.0](https://www.jsonrpc.org/specification) messages over
+//! stdin and stdout, one message per line. Everything the plugin writes to stderr is passed
+//! through to the stderr of OxiRun.
+//!
+//! Host to plugin:
+//! - request `initialize` with `{"config": <entire OxiRun config>, "daemon": false}`, `daemon` is
+//!   true when OxiRun runs as daemon and keeps the plugin running. The plugin responds with
+//!   `{"name": "<name of the plugin>", "actions": ["<action>", ...], "no_query": true}`,
+//!   `actions` is optional and lists the actions users can bind to keys
+//! - notification `query` with `{"text": "<filter text>"}`
+//! - notification `no_query` with `{}` when the filter text is empty, only sent to plugins which
+//!   set `no_query` in their initialize response, the others receive a `query` with empty text
+//! - notification `activate` with `{"id": <entry id>, "token": "<activation token>"}`, the plugin
+//!   launches the entry itself and passes the token on in `XDG_ACTIVATION_TOKEN`. `token` is
+//!   null when the compositor did not issue one. When one of the actions of the entry was
+//!   chosen, `"action": <action id>` is added
+//! - notification `reconfigure` with `{"config": <entire OxiRun config>}` after the config changed
+//! - notification `action` with `{"name": "<action>", "id": <entry id>}`
+//!
+//! Plugin to host:
+//! - notification `results` with `{"results": [<result>, ...]}`, replaces all previous results
+//! - notification `error` with `{"message": "<error>"}`
+//!
+//! A result is `{"id": 1, "title": "Firefox", "subtitle": "Web Browser", "icon": "/path.svg",
+//! "score": 100, "actions": [<action>, ...]}`, `subtitle`, `icon`, `score` and `actions` are
+//! optional. An action is `{"id": 1, "title": "New Window", "icon": "/path.svg", "keep_open": false}`,
+//! the actions are shown in the action menu of the entry and `icon` is optional. OxiRun closes after
+//! activating an action unless `keep_open` is true, which suits actions changing the results.
+//!
+//! Example of a plugin written in shell:
+//! ```sh
+//! #!/bin/sh
+//! while read -r line; do
+//!     case "$line" in
+//!     *'"initialize"'*) echo '{"jsonrpc":"2.0","id":1,"result":{"name":"Hello"}}' ;;
+//!     *'"query"'*) echo '{"jsonrpc":"2.0","method":"results","params":{"results":[{"id":0,"title":"Hello","score":50}]}}' ;;
+//!     *'"activate"'*) notify-send "Hello" ;;
+//!     esac
+//! done
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Sender},
+    },
+    time::Duration,
+};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use toml::Table;
+
+use super::{Plugin, PluginEntry, PluginLoadError, notify};
+
+const INITIALIZE_ID: u64 = 1;
+/// How long a launch waits for the activate message to be written before OxiRun exits
+const WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Deserialize)]
+struct Incoming {
+    id: Option<u64>,
+    method: Option<String>,
+    params: Option<Value>,
+    result: Option<Value>,
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct InitializeResult {
+    name: String,
+    #[serde(default)]
+    actions: Vec<String>,
+    #[serde(default)]
+    no_query: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResultsParams {
+    results: Vec<ProcessEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorParams {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProcessEntry {
+    id: u64,
+    title: String,
+    subtitle: Option<String>,
+    icon: Option<PathBuf>,
+    #[serde(default)]
+    score: i64,
+    #[serde(default)]
+    actions: Vec<ProcessAction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProcessAction {
+    id: u64,
+    title: String,
+    icon: Option<PathBuf>,
+    #[serde(default)]
+    keep_open: bool,
+}
+
+impl From<ProcessAction> for PluginEntry {
+    fn from(action: ProcessAction) -> Self {
+        Self {
+            id: action.id,
+            score: 0,
+            title: action.title,
+            subtitle: None,
+            icon: action.icon,
+        }
+    }
+}
+
+/// Written by the reader thread, picked up by [`ProcessPlugin::update`]
+#[derive(Debug, Default)]
+struct Pending {
+    name: Option<String>,
+    actions: Option<Vec<String>>,
+    no_query: Option<bool>,
+    entries: Option<Vec<ProcessEntry>>,
+    errors: Vec<String>,
+}
+
+type Outgoing = (String, Option<Sender<()>>);
+
+/// A plugin running as a child process, see the module documentation for the protocol.
+pub struct ProcessPlugin {
+    name: String,
+    actions: Vec<String>,
+    /// Whether the plugin understands the `no_query` notification
+    no_query: bool,
+    child: Child,
+    sender: Sender<Outgoing>,
+    pending: Arc<Mutex<Pending>>,
+    entries: Vec<PluginEntry>,
+    entry_actions: HashMap<u64, Vec<PluginEntry>>,
+    /// Entry and action ids of the actions OxiRun stays open after
+    keep_open: HashSet<(u64, u64)>,
+    errors: Vec<String>,
+}
+
+impl ProcessPlugin {
+    fn send(&self, method: &str, params: Value) {
+        let msg = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        let _ = self.sender.send((msg.to_string(), None));
+    }
+
+    /// OxiRun exits right after a launch, make sure the plugin actually receives the message
+    fn activate(&self, params: Value) {
+        let msg = json!({ "jsonrpc": "2.0", "method": "activate", "params": params });
+        let (ack_sender, ack_receiver) = mpsc::channel();
+        if self
+            .sender
+            .send((msg.to_string(), Some(ack_sender)))
+            .is_ok()
+        {
+            let _ = ack_receiver.recv_timeout(WRITE_TIMEOUT);
+        }
+    }
+}
+
+impl Plugin for ProcessPlugin {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn update(&mut self) {
+        if let Ok(mut pending) = self.pending.lock() {
+            if let Some(name) = pending.name.take() {
+                self.name = name;
+            }
+            if let Some(actions) = pending.actions.take() {
+                self.actions = actions;
+            }
+            if let Some(no_query) = pending.no_query.take() {
+                self.no_query = no_query;
+            }
+            if let Some(entries) = pending.entries.take() {
+                self.entry_actions.clear();
+                self.keep_open.clear();
+                self.entries = entries
+                    .into_iter()
+                    .map(|entry| {
+                        let keep_open = entry.actions.iter().filter(|action| action.keep_open);
+                        self.keep_open
+                            .extend(keep_open.map(|action| (entry.id, action.id)));
+                        let actions = entry.actions.into_iter().map(Into::into).collect();
+                        self.entry_actions.insert(entry.id, actions);
+                        PluginEntry {
+                            id: entry.id,
+                            score: entry.score,
+                            title: entry.title,
+                            subtitle: entry.subtitle,
+                            icon: entry.icon,
+                        }
+                    })
+                    .collect();
+            }
+            self.errors.append(&mut pending.errors);
+        }
+    }
+
+    fn query(&mut self, filter_text: &str) {
+        self.send("query", json!({ "text": filter_text }));
+    }
+
+    fn no_query(&mut self) {
+        if self.no_query {
+            self.send("no_query", json!({}));
+        } else {
+            self.query("");
+        }
+    }
+
+    fn launch(&mut self, entry_id: u64, activation_token: Option<&str>) {
+        self.activate(json!({ "id": entry_id, "token": activation_token }));
+    }
+
+    fn entries(&self) -> Vec<PluginEntry> {
+        self.entries.clone()
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+
+    fn reconfigure(&mut self, config: &Table) -> bool {
+        self.send("reconfigure", json!({ "config": config }));
+        true
+    }
+
+    fn actions(&self) -> Vec<String> {
+        self.actions.clone()
+    }
+
+    fn run_action(&mut self, action: &str, entry_id: u64) {
+        self.send("action", json!({ "name": action, "id": entry_id }));
+    }
+
+    fn entry_actions(&self, entry_id: u64) -> Vec<PluginEntry> {
+        self.entry_actions
+            .get(&entry_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn launch_action(
+        &mut self,
+        entry_id: u64,
+        action_id: u64,
+        activation_token: Option<&str>,
+    ) -> bool {
+        self.activate(json!({ "id": entry_id, "action": action_id, "token": activation_token }));
+        !self.keep_open.contains(&(entry_id, action_id))
+    }
+}
+
+impl Drop for ProcessPlugin {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn write_messages(mut stdin: ChildStdin, receiver: mpsc::Receiver<Outgoing>) {
+    for (msg, ack) in receiver {
+        if writeln!(stdin, "{msg}")
+            .and_then(|_| stdin.flush())
+            .is_err()
+        {
+            break;
+        }
+        if let Some(ack) = ack {
+            let _ = ack.send(());
+        }
+    }
+}
+
+fn handle_message(pending: &Mutex<Pending>, msg: Incoming) {
+    let Ok(mut pending) = pending.lock() else {
+        return;
+    };
+    if let Some(error) = msg.error {
+        pending.errors.push(error.message);
+        return;
+    }
+    if msg.id == Some(INITIALIZE_ID) {
+        match msg.result.map(serde_json::from_value::<InitializeResult>) {
+            Some(Ok(result)) => {
+                pending.name = Some(result.name);
+                pending.actions = Some(result.actions);
+                pending.no_query = Some(result.no_query);
+            }
+            _ => pending.errors.push("Invalid initialize response".into()),
+        }
+        return;
+    }
+    let params = msg.params.unwrap_or_default();
+    match msg.method.as_deref() {
+        Some("results") => match serde_json::from_value::<ResultsParams>(params) {
+            Ok(params) => pending.entries = Some(params.results),
+            Err(error) => pending.errors.push(format!("Invalid results: {error}")),
+        },
+        Some("error") => match serde_json::from_value::<ErrorParams>(params) {
+            Ok(params) => pending.errors.push(params.message),
+            Err(error) => pending.errors.push(format!("Invalid error: {error}")),
+        },
+        Some(method) => pending.errors.push(format!("Unknown method {method}")),
+        None => (),
+    }
+}
+
+fn read_messages(index: usize, stdout: impl BufRead, pending: Arc<Mutex<Pending>>) {
+    for line in stdout.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Incoming>(&line) {
+            Ok(msg) => handle_message(&pending, msg),
+            Err(error) => {
+                if let Ok(mut pending) = pending.lock() {
+                    pending.errors.push(format!("Invalid message: {error}"));
+                }
+            }
+        }
+        notify(index);
+    }
+    if let Ok(mut pending) = pending.lock() {
+        pending.errors.push("Plugin process exited".into());
+    }
+    notify(index);
+}
+
+/// Starts the plugin executable at the given path, `index` is used to route notifications of the
+/// plugin. All communication happens on separate threads, a hanging plugin never blocks OxiRun.
+pub fn spawn_plugin(
+    path: &Path,
+    index: usize,
+    config: &Table,
+    daemon: bool,
+) -> Result<ProcessPlugin, PluginLoadError> {
+    let mut child = Command::new(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(PluginLoadError::Spawn)?;
+    let stdin = child.stdin.take().expect("stdin is piped");
+    let stdout = child.stdout.take().expect("stdout is piped");
+
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || write_messages(stdin, receiver));
+
+    let pending = Arc::new(Mutex::new(Pending::default()));
+    let reader_pending = pending.clone();
+    std::thread::spawn(move || read_messages(index, BufReader::new(stdout), reader_pending));
+
+    let initialize = json!({
+        "jsonrpc": "2.0",
+        "id": INITIALIZE_ID,
+        "method": "initialize",
+        "params": { "config": config, "daemon": daemon },
+    });
+    let _ = sender.send((initialize.to_string(), None));
+
+    Ok(ProcessPlugin {
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        actions: Vec::new(),
+        no_query: false,
+        child,
+        sender,
+        pending,
+        entries: Vec::new(),
+        entry_actions: HashMap::new(),
+        keep_open: HashSet::new(),
+        errors: Vec::new(),
+    })
+}
@@ -16,7 +16,7 @@ impl FocusDirection {
                 if rhs > 0 {
                     rhs - 1
                 } else {
-                    length - 1
+                    length.saturating_sub(1)
                 }
             }
             FocusDirection::Down => {
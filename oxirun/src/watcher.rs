@@ -0,0 +1,63 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use iced::futures::{
+    Stream,
+    channel::mpsc::{UnboundedSender, unbounded},
+};
+use inotify::{Inotify, WatchMask};
+
+use crate::{CLI, config::config_path};
+
+/// Yields whenever the config file was written, replaced or removed.
+/// Yields an error instead once the file can not be watched.
+pub fn config_changes() -> impl Stream<Item = Result<(), String>> {
+    let (sender, receiver) = unbounded();
+    std::thread::spawn(move || {
+        let result = config_path(&CLI)
+            .map_err(|error| error.to_string())
+            .and_then(|path| {
+                watch_config(path, &sender)
+                    .map_err(|error| format!("Could not watch the config file: {error}"))
+            });
+        if let Err(error) = result {
+            let _ = sender.unbounded_send(Err(error));
+        }
+    });
+    receiver
+}
+
+fn watch_config(
+    path: PathBuf,
+    sender: &UnboundedSender<Result<(), String>>,
+) -> std::io::Result<()> {
+    let (Some(dir), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            "Config path has no parent directory",
+        ));
+    };
+    // a relative path without directory is relative to the working directory
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let mut inotify = Inotify::init()?;
+    // editors commonly replace the file instead of writing to it, which only the directory sees
+    inotify.watches().add(
+        dir,
+        WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::DELETE,
+    )?;
+    let mut buffer = [0; 4096];
+    loop {
+        let mut events = inotify.read_events_blocking(&mut buffer)?;
+        if events.any(|event| event.name == Some(file_name))
+            && sender.unbounded_send(Ok(())).is_err()
+        {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! On-disk cache of the desktop files and icons read by [`crate::fetch_entries`].
+//!
+//! Stored in `$XDG_CACHE_HOME/oxirun/applications.json`. Every directory is cached together with
+//! the mtime it had when it was read and is only read again once its mtime changed, which happens
+//! whenever a file in it is added, removed or renamed. Files edited in place are not noticed,
+//! package managers replace them instead.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{EntryInfo, IconVariant, ReadOptions, state_file::StateFile};
+
+/// Increase whenever the cached types change, older caches are discarded
+const CACHE_VERSION: u32 = 2;
+
+/// The desktop files of a single directory, parsed with the options of the cache
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntryDir {
+    /// Desktop file ids and their entries, None for hidden and invalid files
+    pub entries: Vec<(String, Option<EntryInfo>)>,
+    /// Names of the subdirectories
+    pub subdirs: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedDir<T> {
+    mtime: SystemTime,
+    content: T,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheData {
+    version: u32,
+    /// The entries depend on these, a change discards all entry dirs
+    options: ReadOptions,
+    entry_dirs: HashMap<PathBuf, CachedDir<EntryDir>>,
+    icon_dirs: HashMap<PathBuf, CachedDir<HashMap<String, IconVariant>>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Cache {
+    file: StateFile,
+    data: CacheData,
+    /// The existing directories read since [`Cache::begin`]
+    visited: HashSet<PathBuf>,
+}
+
+/// Returns the cached content of the directory if its mtime is unchanged, reads it otherwise
+fn cached<T: Clone>(
+    dirs: &mut HashMap<PathBuf, CachedDir<T>>,
+    visited: &mut HashSet<PathBuf>,
+    dir: &Path,
+    read: impl FnOnce(&Path) -> T,
+) -> T {
+    // taken before reading, a change while reading is noticed the next time
+    let Ok(mtime) = fs::metadata(dir).and_then(|metadata| metadata.modified()) else {
+        return read(dir);
+    };
+    visited.insert(dir.to_path_buf());
+    if let Some(cached) = dirs.get(dir)
+        && cached.mtime == mtime
+    {
+        return cached.content.clone();
+    }
+    let content = read(dir);
+    dirs.insert(
+        dir.to_path_buf(),
+        CachedDir {
+            mtime,
+            content: content.clone(),
+        },
+    );
+    content
+}
+
+impl Cache {
+    /// Reads the cache file, a missing or outdated cache is empty
+    pub fn load() -> Self {
+        let file = StateFile::cache("applications.json");
+        let data = file
+            .read()
+            .ok()
+            .flatten()
+            .and_then(|data| serde_json::from_str::<CacheData>(&data).ok())
+            .filter(|data| data.version == CACHE_VERSION)
+            .unwrap_or_default();
+        Self {
+            file,
+            data,
+            visited: HashSet::new(),
+        }
+    }
+
+    /// Starts reading the data dirs with the given options
+    pub fn begin(&mut self, options: &ReadOptions) {
+        if self.data.options != *options {
+            self.data.options = options.clone();
+            self.data.entry_dirs.clear();
+        }
+        self.visited.clear();
+    }
+
+    pub fn entry_dir(&mut self, dir: &Path, read: impl FnOnce(&Path) -> EntryDir) -> EntryDir {
+        cached(&mut self.data.entry_dirs, &mut self.visited, dir, read)
+    }
+
+    pub fn icon_dir(
+        &mut self,
+        dir: &Path,
+        read: impl FnOnce(&Path) -> HashMap<String, IconVariant>,
+    ) -> HashMap<String, IconVariant> {
+        cached(&mut self.data.icon_dirs, &mut self.visited, dir, read)
+    }
+
+    /// Records a directory which is read every time instead of being cached
+    pub fn visit(&mut self, dir: &Path) {
+        self.visited.insert(dir.to_path_buf());
+    }
+
+    /// The directories read since [`Cache::begin`], changes in any other directory do not affect
+    /// the entries
+    pub fn visited(&self) -> impl Iterator<Item = &Path> {
+        self.visited.iter().map(PathBuf::as_path)
+    }
+
+    /// Drops the directories which were not read since [`Cache::begin`] and writes the cache file
+    pub fn save(&mut self) -> Result<(), String> {
+        self.data.version = CACHE_VERSION;
+        self.data
+            .entry_dirs
+            .retain(|dir, _| self.visited.contains(dir));
+        self.data
+            .icon_dirs
+            .retain(|dir, _| self.visited.contains(dir));
+        let data = serde_json::to_string(&self.data)
+            .map_err(|error| format!("Could not serialize {}: {error}", self.file))?;
+        self.file.write(&data)
+    }
+}
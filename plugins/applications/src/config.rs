@@ -1,12 +1,40 @@
+use std::path::PathBuf;
+
 use optional_struct::{Applicable, optional_struct};
 use serde::{Deserialize, Serialize};
 use toml::Table;
 
+use crate::launch::Launcher;
+
 #[optional_struct]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub max_entries: usize,
     pub terminal: String,
+    /// Lists entries hidden by `NoDisplay`, `Hidden`, `TryExec`, `OnlyShowIn` and `NotShowIn`
+    pub show_hidden: bool,
+    /// Data dirs searched for applications and icons after the standard ones
+    pub extra_dirs: Vec<PathBuf>,
+    pub launcher: Launcher,
+    pub empty_query: EmptyQuery,
+    /// Desktop file ids ranked above other matches and shown on an empty query, in this order
+    pub pinned: Vec<String>,
+    /// Desktop file ids which are never listed
+    pub hidden: Vec<String>,
+}
+
+/// What is shown while nothing is entered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyQuery {
+    /// The pinned applications followed by the launched ones, the most frecent first
+    #[default]
+    Recent,
+    /// Only the pinned applications
+    Pinned,
+    /// All applications sorted by their name
+    Alphabetical,
+    Nothing,
 }
 
 impl Default for Config {
@@ -14,19 +42,31 @@ impl Default for Config {
         Self {
             max_entries: 7,
             terminal: String::from("kitty"),
+            show_hidden: false,
+            extra_dirs: Vec::new(),
+            launcher: Launcher::default(),
+            empty_query: EmptyQuery::default(),
+            pinned: Vec::new(),
+            hidden: Vec::new(),
         }
     }
 }
 
-pub fn get_config(global_config: Table) -> Config {
+/// Reads the `[applications]` section, an invalid section is reported and replaced by the defaults.
+pub fn get_config(global_config: Table) -> (Config, Option<String>) {
     let default_config = Config::default();
     if let Some(config_value) = global_config.get("applications") {
-        let config: OptionalConfig =
-            // TODO how to not reserialize this?
-            toml::from_str(&toml::to_string(config_value).expect("Could not reserialize"))
-                .expect("Could not deserialize config");
-        config.build(default_config)
+        match config_value.clone().try_into::<OptionalConfig>() {
+            Ok(config) => (config.build(default_config), None),
+            Err(error) => (
+                default_config,
+                Some(format!(
+                    "Invalid [applications] config: {}",
+                    error.to_string().trim_end()
+                )),
+            ),
+        }
     } else {
-        default_config
+        (default_config, None)
     }
 }
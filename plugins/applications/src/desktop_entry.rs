@@ -0,0 +1,349 @@
+//! Parser for desktop entry files.
+//! https://specifications.freedesktop.org/desktop-entry-spec/latest/
+
+use std::{collections::HashMap, env};
+
+pub const DESKTOP_ENTRY_GROUP: &str = "Desktop Entry";
+
+/// A parsed desktop entry file, values are kept raw until they are read with a typed getter.
+#[derive(Debug, Clone, Default)]
+pub struct DesktopFile {
+    groups: Vec<(String, Group)>,
+}
+
+/// The key value pairs of a single group, localized keys are stored with their locale,
+/// e.g. `Name[de_CH]`.
+#[derive(Debug, Clone, Default)]
+pub struct Group {
+    entries: HashMap<String, String>,
+}
+
+impl DesktopFile {
+    /// Returns None if the file does not start with a `[Desktop Entry]` group, only comments and
+    /// blank lines may precede it.
+    pub fn parse(data: &str) -> Option<Self> {
+        let mut groups: Vec<(String, Group)> = Vec::new();
+        for line in data.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                if groups.is_empty() && name != DESKTOP_ENTRY_GROUP {
+                    return None;
+                }
+                groups.push((name.to_string(), Group::default()));
+                continue;
+            }
+            let (_, group) = groups.last_mut()?;
+            if let Some((key, value)) = line.split_once('=') {
+                // the first occurrence wins, later duplicates are invalid anyway
+                group
+                    .entries
+                    .entry(key.trim_end().to_string())
+                    .or_insert_with(|| value.trim_start().to_string());
+            }
+        }
+        if groups.is_empty() {
+            return None;
+        }
+        Some(Self { groups })
+    }
+
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups
+            .iter()
+            .find(|(group_name, _)| group_name == name)
+            .map(|(_, group)| group)
+    }
+
+    /// The `[Desktop Entry]` group, which every parsed file has
+    pub fn entry(&self) -> &Group {
+        &self.groups[0].1
+    }
+}
+
+impl Group {
+    pub fn raw(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(String::as_str)
+    }
+
+    /// A string value with its escape sequences resolved
+    pub fn string(&self, key: &str) -> Option<String> {
+        self.raw(key).map(unescape)
+    }
+
+    /// The value for the best matching locale of `locales`, which is ordered by preference,
+    /// falling back to the unlocalized key
+    pub fn localized(&self, key: &str, locales: &[String]) -> Option<String> {
+        locales
+            .iter()
+            .find_map(|locale| self.string(&format!("{key}[{locale}]")))
+            .or_else(|| self.string(key))
+    }
+
+    /// Only `true` is true, missing and invalid values are false
+    pub fn boolean(&self, key: &str) -> bool {
+        self.raw(key) == Some("true")
+    }
+
+    /// A list separated by `;`, separators escaped as `\;` are part of the value
+    pub fn list(&self, key: &str) -> Vec<String> {
+        self.raw(key).map(split_list).unwrap_or_default()
+    }
+
+    pub fn localized_list(&self, key: &str, locales: &[String]) -> Vec<String> {
+        locales
+            .iter()
+            .find_map(|locale| self.raw(&format!("{key}[{locale}]")))
+            .or_else(|| self.raw(key))
+            .map(split_list)
+            .unwrap_or_default()
+    }
+}
+
+/// Resolves `\s`, `\n`, `\t`, `\r` and `\\`, unknown escapes are kept as they are
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            unescaped.push(char);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut chars = value.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            ';' => items.push(unescape(&std::mem::take(&mut item))),
+            '\\' => match chars.next() {
+                Some(';') => item.push(';'),
+                // the other escapes are resolved per item
+                Some(other) => {
+                    item.push('\\');
+                    item.push(other);
+                }
+                None => item.push('\\'),
+            },
+            _ => item.push(char),
+        }
+    }
+    // the trailing separator is optional
+    if !item.is_empty() {
+        items.push(unescape(&item));
+    }
+    items
+}
+
+/// The locales to look up localized keys with, in the order of preference.
+/// `lang_COUNTRY.ENCODING@MODIFIER` yields `lang_COUNTRY@MODIFIER`, `lang_COUNTRY`,
+/// `lang@MODIFIER` and `lang`, the encoding is never matched.
+pub fn current_locales() -> Vec<String> {
+    locales_from(|var| env::var(var).ok())
+}
+
+/// [`current_locales`] with the environment variables looked up by `var`
+fn locales_from(var: impl Fn(&str) -> Option<String>) -> Vec<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(var)
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    let (locale, modifier) = match locale.split_once('@') {
+        Some((locale, modifier)) => (locale, Some(modifier)),
+        None => (locale.as_str(), None),
+    };
+    let locale = locale.split('.').next().unwrap_or_default();
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        return Vec::new();
+    }
+    let (lang, country) = match locale.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (locale, None),
+    };
+
+    let mut locales = Vec::new();
+    if let Some(country) = country {
+        if let Some(modifier) = modifier {
+            locales.push(format!("{lang}_{country}@{modifier}"));
+        }
+        locales.push(format!("{lang}_{country}"));
+    }
+    if let Some(modifier) = modifier {
+        locales.push(format!("{lang}@{modifier}"));
+    }
+    locales.push(lang.to_string());
+    locales
+}
+
+/// The desktops of `XDG_CURRENT_DESKTOP`, which is a colon separated list
+pub fn current_desktops() -> Vec<String> {
+    env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|desktop| !desktop.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(data: &str) -> DesktopFile {
+        DesktopFile::parse(data).expect("fixture is a desktop entry")
+    }
+
+    fn locales(values: &[&str]) -> Vec<String> {
+        values.iter().map(ToString::to_string).collect()
+    }
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars = vars
+            .iter()
+            .map(|(var, value)| (var.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>();
+        move |var| vars.get(var).cloned()
+    }
+
+    #[test]
+    fn parses_firefox_with_actions() {
+        let file = fixture(include_str!("../tests/fixtures/firefox.desktop"));
+        let entry = file.entry();
+        assert_eq!(entry.string("Name").as_deref(), Some("Firefox"));
+        assert_eq!(entry.raw("Exec"), Some("firefox %u"));
+        assert_eq!(
+            entry.list("Actions"),
+            ["new-window", "new-private-window", "profile-manager-window"]
+        );
+        let action = file
+            .group("Desktop Action new-private-window")
+            .expect("action group");
+        assert_eq!(action.raw("Exec"), Some("firefox --private-window %u"));
+        assert_eq!(
+            action.localized("Name", &locales(&["de"])).as_deref(),
+            Some("Ein neues privates Fenster öffnen")
+        );
+        assert!(file.group("Desktop Action missing").is_none());
+    }
+
+    #[test]
+    fn skips_leading_comments() {
+        let file = fixture(include_str!("../tests/fixtures/leading-comment.desktop"));
+        assert_eq!(file.entry().string("Name").as_deref(), Some("Dolphin"));
+    }
+
+    #[test]
+    fn rejects_files_not_starting_with_desktop_entry() {
+        let data = include_str!("../tests/fixtures/no-desktop-entry.desktop");
+        assert!(DesktopFile::parse(data).is_none());
+        assert!(DesktopFile::parse("# only a comment\n").is_none());
+        assert!(DesktopFile::parse("Name=No group\n[Desktop Entry]\n").is_none());
+    }
+
+    #[test]
+    fn prefers_the_most_specific_locale() {
+        let file = fixture(include_str!("../tests/fixtures/localized.desktop"));
+        let entry = file.entry();
+        let name = |values: &[&str]| entry.localized("Name", &locales(values));
+        assert_eq!(name(&["de_CH", "de"]).as_deref(), Some("Taschenrechner"));
+        assert_eq!(name(&["de_AT", "de"]).as_deref(), Some("Rechner"));
+        assert_eq!(name(&["sr@latin", "sr"]).as_deref(), Some("Kalkulator"));
+        assert_eq!(name(&["fr_FR", "fr"]).as_deref(), Some("Calculator"));
+        assert_eq!(name(&[]).as_deref(), Some("Calculator"));
+        assert_eq!(
+            entry.localized_list("Keywords", &locales(&["de_CH", "de"])),
+            ["Berechnung", "Arithmetik"]
+        );
+        assert_eq!(
+            entry.localized_list("Keywords", &locales(&["fr"])),
+            ["calculation", "arithmetic"]
+        );
+    }
+
+    #[test]
+    fn resolves_escapes() {
+        let file = fixture(include_str!("../tests/fixtures/escapes.desktop"));
+        assert_eq!(
+            file.entry().string("Comment").as_deref(),
+            Some("one two\nthree\tfour\\five")
+        );
+    }
+
+    #[test]
+    fn splits_lists() {
+        let file = fixture(include_str!("../tests/fixtures/escapes.desktop"));
+        let entry = file.entry();
+        assert_eq!(
+            entry.list("Keywords"),
+            ["semi;colon", "back\\slash", "plain"]
+        );
+        // the trailing separator is optional
+        assert_eq!(entry.list("Categories"), ["Utility"]);
+        assert!(entry.list("Missing").is_empty());
+    }
+
+    #[test]
+    fn only_true_is_true() {
+        let file = fixture(include_str!("../tests/fixtures/localized.desktop"));
+        let entry = file.entry();
+        assert!(entry.boolean("DBusActivatable"));
+        assert!(!entry.boolean("NoDisplay"));
+        assert!(!entry.boolean("Hidden"));
+        assert!(!entry.boolean("Missing"));
+    }
+
+    #[test]
+    fn locales_fall_back_from_country_and_modifier() {
+        assert_eq!(
+            locales_from(env(&[("LANG", "de_CH.UTF-8@euro")])),
+            ["de_CH@euro", "de_CH", "de@euro", "de"]
+        );
+        assert_eq!(
+            locales_from(env(&[("LANG", "sr@latin")])),
+            ["sr@latin", "sr"]
+        );
+        assert_eq!(
+            locales_from(env(&[("LANG", "fr_FR.UTF-8")])),
+            ["fr_FR", "fr"]
+        );
+        assert!(locales_from(env(&[("LANG", "C.UTF-8")])).is_empty());
+        assert!(locales_from(env(&[])).is_empty());
+    }
+
+    #[test]
+    fn locale_variables_are_used_in_order() {
+        let all = [
+            ("LC_ALL", "de_DE"),
+            ("LC_MESSAGES", "fr_FR"),
+            ("LANG", "it_IT"),
+        ];
+        assert_eq!(locales_from(env(&all)), ["de_DE", "de"]);
+        assert_eq!(locales_from(env(&all[1..])), ["fr_FR", "fr"]);
+        assert_eq!(locales_from(env(&all[2..])), ["it_IT", "it"]);
+        // empty variables are skipped
+        let empty = [("LC_ALL", ""), ("LANG", "it_IT")];
+        assert_eq!(locales_from(env(&empty)), ["it_IT", "it"]);
+    }
+}
@@ -0,0 +1,200 @@
+//! The Exec key of desktop entries.
+//! https://specifications.freedesktop.org/desktop-entry-spec/latest/exec-variables.html
+
+use std::path::Path;
+
+/// Characters which have to be escaped with a backslash inside a quoted argument
+const QUOTED_ESCAPES: [char; 4] = ['"', '`', '$', '\\'];
+
+/// What the field codes of an Exec key are replaced with
+pub struct FieldValues<'a> {
+    /// Translated name of the application, `%c`
+    pub name: &'a str,
+    /// Icon key of the desktop entry, `%i`
+    pub icon: Option<&'a str>,
+    /// Path of the desktop file, `%k`
+    pub location: &'a Path,
+}
+
+/// Splits an Exec value into its arguments, the general escape sequences of desktop entry values
+/// have to be resolved already. Returns None if a quote is not terminated.
+pub fn split_exec(exec: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut in_arg = false;
+    let mut chars = exec.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            ' ' | '\t' | '\n' => {
+                if in_arg {
+                    args.push(std::mem::take(&mut arg));
+                    in_arg = false;
+                }
+            }
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => {
+                            let escaped = chars.next()?;
+                            if !QUOTED_ESCAPES.contains(&escaped) {
+                                arg.push('\\');
+                            }
+                            arg.push(escaped);
+                        }
+                        quoted => arg.push(quoted),
+                    }
+                }
+            }
+            _ => {
+                in_arg = true;
+                arg.push(char);
+            }
+        }
+    }
+    if in_arg {
+        args.push(arg);
+    }
+    Some(args)
+}
+
+/// Replaces the field codes of the arguments, no files or urls are ever passed to an application.
+/// `%i` expands to two arguments, deprecated and unknown codes are removed.
+pub fn expand_field_codes(args: Vec<String>, values: &FieldValues) -> Vec<String> {
+    let mut expanded = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            // the list codes take their whole argument with them
+            "%f" | "%F" | "%u" | "%U" => continue,
+            "%i" => {
+                if let Some(icon) = values.icon {
+                    expanded.push(String::from("--icon"));
+                    expanded.push(icon.to_string());
+                }
+                continue;
+            }
+            _ => (),
+        }
+        let mut result = String::with_capacity(arg.len());
+        let mut chars = arg.chars();
+        while let Some(char) = chars.next() {
+            if char != '%' {
+                result.push(char);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => result.push('%'),
+                Some('c') => result.push_str(values.name),
+                Some('k') => result.push_str(&values.location.to_string_lossy()),
+                _ => (),
+            }
+        }
+        expanded.push(result);
+    }
+    expanded
+}
+
+/// Splits the Exec value and expands its field codes, returns None if it is invalid or empty
+pub fn parse_exec(exec: &str, values: &FieldValues) -> Option<Vec<String>> {
+    let args = expand_field_codes(split_exec(exec)?, values);
+    (!args.is_empty()).then_some(args)
+}
+
+/// Joins the arguments into a command line which a shell splits into the same arguments
+pub fn join_args(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            let is_plain = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|char| char.is_ascii_alphanumeric() || "-_./=:,+@%".contains(char));
+            if is_plain {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(icon: Option<&str>) -> FieldValues<'_> {
+        FieldValues {
+            name: "Text Editor",
+            icon,
+            location: Path::new("/usr/share/applications/editor.desktop"),
+        }
+    }
+
+    #[test]
+    fn splits_quoted_arguments() {
+        assert_eq!(
+            split_exec(r#"sh -c "echo hello  world" last"#).unwrap(),
+            ["sh", "-c", "echo hello  world", "last"]
+        );
+        assert_eq!(split_exec(r#"a"b c"d"#).unwrap(), ["ab cd"]);
+        assert_eq!(split_exec(r#"app """#).unwrap(), ["app", ""]);
+        assert_eq!(split_exec("  app\t--flag  ").unwrap(), ["app", "--flag"]);
+    }
+
+    #[test]
+    fn resolves_quoted_escapes() {
+        assert_eq!(
+            split_exec(r#"app "say \"hi\"" "C:\\dir" "\$HOME" "\`id\`""#).unwrap(),
+            ["app", r#"say "hi""#, r"C:\dir", "$HOME", "`id`"]
+        );
+        // other backslashes are kept as they are
+        assert_eq!(split_exec(r#"app "a\nb""#).unwrap(), ["app", r"a\nb"]);
+    }
+
+    #[test]
+    fn rejects_unterminated_quotes() {
+        assert_eq!(split_exec(r#"app "open"#), None);
+        assert_eq!(split_exec(r#"app "open\"#), None);
+    }
+
+    #[test]
+    fn expands_percent_sign() {
+        let args = split_exec("printf 100%% %%c").unwrap();
+        assert_eq!(
+            expand_field_codes(args, &values(None)),
+            ["printf", "100%", "%c"]
+        );
+    }
+
+    #[test]
+    fn expands_name_location_and_icon() {
+        let args = split_exec(r#"editor %i --title="%c" --desktop-file=%k"#).unwrap();
+        assert_eq!(
+            expand_field_codes(args, &values(Some("accessories-text-editor"))),
+            [
+                "editor",
+                "--icon",
+                "accessories-text-editor",
+                "--title=Text Editor",
+                "--desktop-file=/usr/share/applications/editor.desktop",
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_icon_without_icon_key() {
+        let args = split_exec("editor %i --new").unwrap();
+        assert_eq!(expand_field_codes(args, &values(None)), ["editor", "--new"]);
+    }
+
+    #[test]
+    fn drops_file_and_url_codes() {
+        let args = split_exec("viewer %f %F %u %U --flag %d%n").unwrap();
+        assert_eq!(
+            expand_field_codes(args, &values(None)),
+            ["viewer", "--flag", ""]
+        );
+        assert_eq!(parse_exec("%U", &values(None)), None);
+    }
+}
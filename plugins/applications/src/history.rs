@@ -0,0 +1,105 @@
+//! Launch history of the applications, ranks frequently and recently launched ones higher.
+//!
+//! Stored in `$XDG_STATE_HOME/oxirun/history`, one line per desktop file id:
+//! `<launch count>\t<last launch as unix timestamp>\t<desktop file id>`
+
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::state_file::StateFile;
+
+const DAY: u64 = 60 * 60 * 24;
+/// Weight of a launch by the days since the last launch, older launches weigh the last value
+const RECENCY_WEIGHTS: [(u64, i64); 4] = [(4, 100), (14, 70), (31, 50), (90, 30)];
+const OLD_WEIGHT: i64 = 10;
+/// The frecency is scaled down by this to be comparable to the fuzzy match scores
+const BOOST_DIVISOR: i64 = 20;
+const MAX_BOOST: i64 = 50;
+
+#[derive(Debug, Clone, Copy)]
+struct Launches {
+    count: u64,
+    last: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    file: StateFile,
+    launches: HashMap<String, Launches>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+impl History {
+    /// Reads the history file, a missing file is an empty history.
+    /// Invalid lines are skipped, they are dropped with the next launch.
+    pub fn load() -> (Self, Option<String>) {
+        let mut history = Self {
+            file: StateFile::state("history"),
+            launches: HashMap::new(),
+        };
+        let data = match history.file.read() {
+            Ok(Some(data)) => data,
+            Ok(None) => return (history, None),
+            Err(error) => return (history, Some(error)),
+        };
+        for line in data.lines() {
+            let mut fields = line.splitn(3, '\t');
+            let (Some(count), Some(last), Some(id)) = (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            if let (Ok(count), Ok(last)) = (count.parse(), last.parse()) {
+                history
+                    .launches
+                    .insert(id.to_string(), Launches { count, last });
+            }
+        }
+        (history, None)
+    }
+
+    /// Counts a launch of the application and writes the history file
+    pub fn record(&mut self, id: &str) -> Result<(), String> {
+        let launches = self
+            .launches
+            .entry(id.to_string())
+            .or_insert(Launches { count: 0, last: 0 });
+        launches.count += 1;
+        launches.last = now();
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let data = self
+            .launches
+            .iter()
+            .map(|(id, launches)| format!("{}\t{}\t{id}\n", launches.count, launches.last))
+            .collect::<String>();
+        self.file.write(&data)
+    }
+
+    /// Launch count weighted by how recently the application was last launched
+    pub fn frecency(&self, id: &str) -> i64 {
+        let Some(launches) = self.launches.get(id) else {
+            return 0;
+        };
+        let age = now().saturating_sub(launches.last) / DAY;
+        let weight = RECENCY_WEIGHTS
+            .iter()
+            .find(|(days, _)| age < *days)
+            .map_or(OLD_WEIGHT, |(_, weight)| *weight);
+        launches.count as i64 * weight
+    }
+
+    /// Added to the fuzzy match score of the application
+    pub fn boost(&self, id: &str) -> i64 {
+        (self.frecency(id) / BOOST_DIVISOR).min(MAX_BOOST)
+    }
+}
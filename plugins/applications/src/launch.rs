@@ -0,0 +1,124 @@
+//! Starting applications detached from OxiRun.
+
+use std::{
+    os::unix::process::CommandExt,
+    path::Path,
+    process::{Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// How applications are started, set with `launcher` in `[applications]`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Launcher {
+    /// Double fork into a new session, the application is reparented to init
+    #[default]
+    Direct,
+    /// Like direct, but the application runs in its own `systemd-run --user --scope` unit and its
+    /// output goes to the journal
+    Systemd,
+}
+
+/// Everything needed to start an application
+pub struct LaunchRequest<'a> {
+    pub args: &'a [String],
+    /// Desktop file id, the systemd unit is named after it
+    pub id: &'a str,
+    /// The `Path` key of the desktop entry
+    pub working_dir: Option<&'a Path>,
+    /// Issued by the compositor for this launch, the compositor only grants focus to applications
+    /// presenting one
+    pub activation_token: Option<&'a str>,
+}
+
+/// Starts the application and returns once its program was executed.
+/// Applications started directly have no output, nothing would read it after OxiRun closed.
+pub fn launch(launcher: Launcher, request: &LaunchRequest) -> Result<(), String> {
+    let Some((program, args)) = request.args.split_first() else {
+        return Err(String::from("The command is empty"));
+    };
+    let mut command = match launcher {
+        Launcher::Direct => {
+            let mut command = Command::new(program);
+            command.args(args);
+            command
+        }
+        Launcher::Systemd => {
+            let mut command = Command::new("systemd-run");
+            command
+                .args(["--user", "--scope", "--quiet", "--collect"])
+                .arg(format!("--unit={}", unit_name(request.id)))
+                .arg("--")
+                .arg("systemd-cat")
+                .arg(format!("--identifier={}", app_id(request.id)))
+                .arg("--")
+                .arg(program)
+                .args(args);
+            command
+        }
+    };
+    // systemd-cat connects the output of systemd units to the journal instead
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(working_dir) = request.working_dir {
+        command.current_dir(working_dir);
+    }
+    // the token OxiRun was started with has already been used
+    match request.activation_token {
+        Some(token) => command
+            .env("XDG_ACTIVATION_TOKEN", token)
+            .env("DESKTOP_STARTUP_ID", token),
+        None => command
+            .env_remove("XDG_ACTIVATION_TOKEN")
+            .env_remove("DESKTOP_STARTUP_ID"),
+    };
+    // the child starts a new session and forks again, the grandchild executes the program.
+    // spawn only returns once the grandchild executed the program or failed to do so, as the
+    // error pipe of std stays open in the grandchild until then.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            match libc::fork() {
+                -1 => Err(std::io::Error::last_os_error()),
+                0 => Ok(()),
+                _ => libc::_exit(0),
+            }
+        });
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|error| format!("Failed to launch {program}: {error}"))?;
+    // reaps the intermediate child, which exits right away
+    let _ = child.wait();
+    Ok(())
+}
+
+/// The desktop file id without its extension
+fn app_id(desktop_id: &str) -> &str {
+    desktop_id.strip_suffix(".desktop").unwrap_or(desktop_id)
+}
+
+/// `app-oxirun-<escaped id>-<random>.scope` as described in
+/// https://systemd.io/DESKTOP_ENVIRONMENTS/
+fn unit_name(desktop_id: &str) -> String {
+    let escaped = app_id(desktop_id)
+        .bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_' | b'.' | b':' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("\\x{byte:02x}"),
+        })
+        .collect::<String>();
+    let random = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.subsec_nanos())
+        .unwrap_or_default();
+    format!("app-oxirun-{escaped}-{random:x}.scope")
+}
@@ -1,53 +1,237 @@
 use std::{
-    collections::HashMap,
+    cmp::Reverse,
+    collections::{HashMap, hash_map},
     env,
     fmt::Debug,
     fs::{self, DirEntry},
-    io::BufRead,
-    path::PathBuf,
-    process::Command,
-    sync::{Arc, RwLock},
+    hash::{DefaultHasher, Hash, Hasher},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread::JoinHandle,
 };
 
-use config::{Config, get_config};
+use cache::{Cache, EntryDir};
+use config::{Config, EmptyQuery, get_config};
+use desktop_entry::{DesktopFile, Group, current_desktops, current_locales};
+use exec::{FieldValues, join_args, parse_exec, split_exec};
 use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
-use iced::{
-    Alignment, Element, Length, Task,
-    widget::{Row, container, text},
-};
-use oxiced::any_send::OxiAny;
+use history::History;
+use launch::{LaunchRequest, Launcher, launch};
+use oxirun_plugin::{Context, Entry, EntryAction, Plugin, export_plugin};
+use serde::{Deserialize, Serialize};
 use toml::Table;
+use user_lists::UserLists;
+use watch::{StopHandle, Watch, Watcher, link_watches};
 
+mod cache;
 mod config;
+mod desktop_entry;
+mod exec;
+mod history;
+mod launch;
+mod state_file;
+mod user_lists;
+mod watch;
 
 const SVG_ENDING: &str = ".svg";
 const PNG_ENDING: &str = ".png";
-
-// https://specifications.freedesktop.org/desktop-entry-spec/latest/exec-variables.html
-const FREEDESKTOP_FIELDS: [&str; 13] = [
-    "%f", "%F", "%u", "%U", "%d", "%D", "%n", "%N", "%i", "%c", "%k", "%v", "%m",
+// TODO make this use the current theme from gtk ?
+// perhaps make kde configurable? or get from env?
+const ICON_THEMES: [&str; 2] = ["hicolor", "Adwaita"];
+
+/// Share dirs of flatpak and Nix, relative to the home directory or absolute.
+/// Launchers started from a minimal compositor config often lack these in `XDG_DATA_DIRS`.
+const EXTRA_DATA_DIRS: [&str; 6] = [
+    ".local/share/flatpak/exports/share",
+    "/var/lib/flatpak/exports/share",
+    ".nix-profile/share",
+    "/etc/profiles/per-user/$USER/share",
+    "/nix/var/nix/profiles/default/share",
+    "/run/current-system/sw/share",
 ];
 
-const DATA_DIRS: [&str; 2] = ["XDG_DATA_DIRS", "XDG_DATA_HOME"];
-
-const ICON_SIZE: f32 = 60.0;
 const SORT_THRESHOLD: i64 = 25;
+/// Added to the score of pinned applications while nothing is entered, above any history boost
+const PINNED_BOOST: i64 = 200;
+
+// ids of the entry actions offered for every application
+const RUN_ACTION: u64 = 0;
+const RUN_IN_TERMINAL_ACTION: u64 = 1;
+const COPY_COMMAND_ACTION: u64 = 2;
+const OPEN_FOLDER_ACTION: u64 = 3;
+const PIN_ACTION: u64 = 4;
+const HIDE_ACTION: u64 = 5;
+/// Id of the first desktop action of an application, the others follow in the order of `Actions`
+const DESKTOP_ACTION_OFFSET: u64 = 100;
 
 #[derive(Default)]
 pub struct Model {
     config: Config,
+    /// None while nothing is entered
+    filter_text: Option<String>,
     applications: Vec<EntryInfo>,
     sorted_applications: Vec<ScoredEntryInfo>,
     fuzzy_matcher: Arc<SkimMatcherV2>,
+    history: History,
+    user_lists: UserLists,
     errors: Vec<String>,
+    fetch_handle: Option<JoinHandle<()>>,
+    /// Set while the data dirs are watched for changes
+    stop_watching: Option<StopHandle>,
 }
 
 impl Model {
-    pub fn new(global_config: Table) -> Model {
-        let config = get_config(global_config);
-        Model {
-            config,
-            ..Default::default()
+    fn sort(&mut self) {
+        let pinned = self.pinned();
+        self.sorted_applications = match &self.filter_text {
+            Some(filter_text) => sort_appliations(
+                self.applications.clone(),
+                filter_text.clone(),
+                self.fuzzy_matcher.clone(),
+                &self.history,
+                &pinned,
+            ),
+            None => empty_query_applications(
+                &self.applications,
+                self.config.empty_query,
+                &self.history,
+                &pinned,
+            ),
+        };
+    }
+
+    /// The pinned applications of the config followed by the ones pinned from the launcher
+    fn pinned(&self) -> Vec<String> {
+        let user_pinned = self
+            .user_lists
+            .pinned()
+            .iter()
+            .filter(|id| !self.config.pinned.contains(id));
+        self.config
+            .pinned
+            .iter()
+            .chain(user_pinned)
+            .cloned()
+            .collect()
+    }
+
+    fn hidden(&self) -> Vec<String> {
+        let user_hidden = self
+            .user_lists
+            .hidden()
+            .iter()
+            .filter(|id| !self.config.hidden.contains(id));
+        self.config
+            .hidden
+            .iter()
+            .chain(user_hidden)
+            .cloned()
+            .collect()
+    }
+
+    /// Pins the application of the entry, or unpins it if it was pinned from the launcher
+    fn toggle_pin(&mut self, entry_id: u64) {
+        let Some(id) = self.application(entry_id).map(|entry| entry.id.clone()) else {
+            self.errors.push("Could not get entry for id".into());
+            return;
+        };
+        let result = if self.config.pinned.contains(&id) {
+            Err(format!("{id} is pinned in the config"))
+        } else if self.user_lists.pinned().contains(&id) {
+            self.user_lists.unpin(&id)
+        } else {
+            self.user_lists.pin(&id)
+        };
+        if let Err(error) = result {
+            self.errors.push(error);
+        }
+        self.sort();
+    }
+
+    /// Hides the application of the entry until it is removed from the state file again
+    fn hide(&mut self, entry_id: u64) {
+        let Some(id) = self.application(entry_id).map(|entry| entry.id.clone()) else {
+            self.errors.push("Could not get entry for id".into());
+            return;
+        };
+        if let Err(error) = self.user_lists.hide(&id) {
+            self.errors.push(error);
+        }
+        self.applications.retain(|entry| entry.id != id);
+        self.sort();
+    }
+
+    /// Runs a command of the application with the configured launcher, in the configured terminal
+    /// if requested
+    fn run_exec(
+        &mut self,
+        entry: &EntryInfo,
+        exec: &[String],
+        terminal: bool,
+        activation_token: Option<&str>,
+    ) {
+        let args = if terminal {
+            let mut args = split_exec(&self.config.terminal).unwrap_or_default();
+            args.extend_from_slice(exec);
+            args
+        } else {
+            exec.to_vec()
+        };
+        let request = LaunchRequest {
+            args: &args,
+            id: &entry.id,
+            working_dir: entry.working_dir.as_deref(),
+            activation_token,
+        };
+        let result =
+            launch(self.config.launcher, &request).and_then(|_| self.history.record(&entry.id));
+        if let Err(error) = result {
+            self.errors.push(error);
+        }
+    }
+
+    /// The application of an entry together with the command of the entry, which is the one of the
+    /// desktop action for action entries
+    fn command(&self, entry_id: u64) -> Option<(&EntryInfo, &[String])> {
+        let entry = self.application(entry_id)?;
+        match split_entry_id(entry_id).1 {
+            Some(action) => Some((entry, &entry.actions.get(action)?.exec)),
+            None => Some((entry, &entry.exec)),
+        }
+    }
+
+    /// The application of an entry, which is also the one of its desktop action entries
+    fn application(&self, entry_id: u64) -> Option<&EntryInfo> {
+        let (application, _) = split_entry_id(entry_id);
+        self.applications
+            .iter()
+            .find(|entry| entry_id_of(&entry.id, None) == application)
+    }
+
+    /// Starts a helper program detached like an application, which leaves no zombie behind
+    fn spawn(&mut self, program: &str, arg: String, activation_token: Option<&str>) {
+        let args = [program.to_string(), arg];
+        let request = LaunchRequest {
+            args: &args,
+            id: program,
+            working_dir: None,
+            activation_token,
+        };
+        if let Err(error) = launch(Launcher::Direct, &request) {
+            self.errors.push(error);
+        }
+    }
+}
+
+impl Drop for Model {
+    // the library is unloaded after the model is dropped, the fetch thread can't outlive it
+    fn drop(&mut self) {
+        if let Some(stop_watching) = &self.stop_watching {
+            stop_watching.stop();
+        }
+        if let Some(handle) = self.fetch_handle.take() {
+            let _ = handle.join();
         }
     }
 }
@@ -64,28 +248,82 @@ impl Debug for Model {
 #[derive(Clone, Debug)]
 pub enum Message {
     ReceiveEntries(Vec<EntryInfo>),
-    ReceiveSortedEntries(Vec<ScoredEntryInfo>),
+    ReceiveError(String),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum IconVariant {
     Svg(PathBuf),
     Png(PathBuf),
     Invalid,
 }
 
-#[derive(Debug, Clone)]
+impl IconVariant {
+    pub fn path(&self) -> Option<&PathBuf> {
+        match self {
+            IconVariant::Svg(path_buf) | IconVariant::Png(path_buf) => Some(path_buf),
+            IconVariant::Invalid => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EntryInfo {
+    /// Desktop file id, e.g. `org.mozilla.firefox.desktop`
+    pub id: String,
     pub name: String,
+    /// The `Icon` key, resolved into `icon` once all icon dirs are read
+    pub icon_name: Option<String>,
+    #[serde(skip)]
     pub icon: Option<IconVariant>,
     pub categories: Vec<String>,
-    pub exec: String,
+    /// Arguments of the Exec key with its field codes expanded
+    pub exec: Vec<String>,
+    /// The program of the `TryExec` key, the application is hidden while it is not installed
+    pub try_exec: Option<String>,
+    /// Whether the command has to be run in the configured terminal
+    pub terminal: bool,
+    /// The desktop actions listed in the `Actions` key, in the order of that key
+    pub actions: Vec<DesktopAction>,
+    /// Path of the desktop file
+    pub path: PathBuf,
+    /// Working directory of the application, the `Path` key
+    pub working_dir: Option<PathBuf>,
+}
+
+/// A `[Desktop Action ...]` group, launched like the application it belongs to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DesktopAction {
+    pub name: String,
+    pub exec: Vec<String>,
+    pub icon_name: Option<String>,
+    #[serde(skip)]
+    pub icon: Option<IconVariant>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ScoredEntryInfo {
+    /// Stable id of the entry, see [`entry_id_of`]
+    pub id: u64,
     pub score: i64,
-    pub entry: EntryInfo,
+    /// Name of the application, desktop actions are shown as "Application: Action"
+    pub title: String,
+    pub icon: Option<IconVariant>,
+}
+
+/// Applications and their desktop actions are both results. The upper 48 bits of the id are a hash
+/// of the desktop file id, which keeps the id when the data dirs are read again and the
+/// applications are reordered, the lower 16 bits are the index of the action plus one.
+fn entry_id_of(desktop_id: &str, action: Option<usize>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    desktop_id.hash(&mut hasher);
+    (hasher.finish() << 16) | action.map_or(0, |action| action as u64 + 1)
+}
+
+/// Splits the id into the id of the application entry and the index of the action
+fn split_entry_id(entry_id: u64) -> (u64, Option<usize>) {
+    let action = (entry_id & u64::from(u16::MAX)) as usize;
+    (entry_id & !u64::from(u16::MAX), action.checked_sub(1))
 }
 
 fn read_single_icon(
@@ -111,24 +349,9 @@ fn read_single_icon(
     };
 }
 
-fn read_icons_per_dir(path: String) -> HashMap<String, IconVariant> {
+fn read_icons(dir: &Path) -> HashMap<String, IconVariant> {
     let mut map = HashMap::new();
-    // TODO make this use the current theme from gtk ?
-    // perhaps make kde configurable? or get from env?
-    for theme in ["hicolor", "Adwaita"] {
-        if let Ok(dirs) = fs::read_dir(format!("{path}/icons/{theme}")) {
-            for subdir in dirs.flatten() {
-                if let Ok(files) =
-                    fs::read_dir(format!("{}/apps", subdir.path().to_str().unwrap_or("")))
-                {
-                    for file_res in files {
-                        read_single_icon(&mut map, file_res);
-                    }
-                };
-            }
-        }
-    }
-    if let Ok(files) = fs::read_dir(format!("{path}/pixmaps")) {
+    if let Ok(files) = fs::read_dir(dir) {
         for file_res in files {
             read_single_icon(&mut map, file_res);
         }
@@ -136,137 +359,260 @@ fn read_icons_per_dir(path: String) -> HashMap<String, IconVariant> {
     map
 }
 
-fn read_single_entry(
-    config: &Config,
-    iconmap: &HashMap<String, IconVariant>,
-    entries: &mut HashMap<String, EntryInfo>,
-    file: DirEntry,
-) {
-    if let Ok(data) = fs::read(file.path()) {
-        let mut map = HashMap::new();
-        let mut iter = data.lines();
-        let first_line = iter
-            .next()
-            .unwrap_or(Ok("".to_string()))
-            .unwrap_or("".to_string());
-        if first_line != "[Desktop Entry]" && !first_line.starts_with("#") {
-            return;
-        }
-        for line in iter.map_while(Result::ok) {
-            if line.starts_with("[Desktop Action") {
-                break;
-            }
-            if let Some((left, right)) = line.split_once("=") {
-                let key = left.to_string();
-                map.entry(key).or_insert_with(|| right.to_string());
+/// The theme dirs are listed every time, only the icon dirs in them are cached
+fn read_icons_per_dir(cache: &mut Cache, path: &Path) -> HashMap<String, IconVariant> {
+    let mut map = HashMap::new();
+    for theme in ICON_THEMES {
+        let theme_dir = path.join("icons").join(theme);
+        if let Ok(dirs) = fs::read_dir(&theme_dir) {
+            cache.visit(&theme_dir);
+            for subdir in dirs.flatten() {
+                map.extend(cache.icon_dir(&subdir.path().join("apps"), read_icons));
             }
         }
+    }
+    map.extend(cache.icon_dir(&path.join("pixmaps"), read_icons));
+    map
+}
 
-        // NoDisplay is set for applications which should not be shown in a runner
-        if let Some("true") = map.get("NoDisplay").map(String::as_str) {
-            return;
-        }
+/// Looks up an icon name in the icon themes, `icon` can also be a path to the icon
+fn resolve_icon(iconmap: &HashMap<String, IconVariant>, icon: &str) -> IconVariant {
+    if let Some(icon) = iconmap.get(icon) {
+        icon.clone()
+    } else if icon.ends_with(PNG_ENDING) {
+        IconVariant::Png(PathBuf::from(icon))
+    } else if icon.ends_with(SVG_ENDING) {
+        IconVariant::Svg(PathBuf::from(icon))
+    } else {
+        IconVariant::Invalid
+    }
+}
 
-        let exec = map.get("Exec").map(|val| {
-            let mut exec = val.to_string();
-            for field in FREEDESKTOP_FIELDS {
-                // TODO should this be possible to be used with additional text
-                // in the text field?
-                exec = exec.replace(field, "");
-            }
-            if let Some("true") = map.get("Terminal").map(String::as_str) {
-                exec = config.terminal.clone() + " " + &exec;
-            }
-            exec
-        });
-        let name = map.get("Name").map(|val| val.to_string());
-        let icon = map.get("Icon").map(|val| {
-            if let Some(icon) = iconmap.get(val) {
-                icon.clone()
-            } else if val.ends_with(PNG_ENDING) {
-                IconVariant::Png(PathBuf::from(val))
-            } else if val.ends_with(SVG_ENDING) {
-                IconVariant::Svg(PathBuf::from(val))
-            } else {
-                IconVariant::Invalid
-            }
-        });
-        let category_entries = map.get("Categories");
-        let keyword_entries = map.get("Keywords");
-        let categories = category_entries
+/// Everything which decides how the desktop files are read, gathered once per fetch
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadOptions {
+    locales: Vec<String>,
+    /// The desktops of `XDG_CURRENT_DESKTOP`, matched against `OnlyShowIn` and `NotShowIn`
+    desktops: Vec<String>,
+    show_hidden: bool,
+}
+
+/// Whether a program is an executable file, programs without a path are looked up in `PATH`
+fn is_executable(program: &str) -> bool {
+    let is_executable_file = |path: &Path| {
+        fs::metadata(path)
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    };
+    if program.contains('/') {
+        return is_executable_file(Path::new(program));
+    }
+    env::var_os("PATH").is_some_and(|path| {
+        env::split_paths(&path).any(|dir| is_executable_file(&dir.join(program)))
+    })
+}
+
+/// Applies `NoDisplay`, `Hidden`, `OnlyShowIn` and `NotShowIn`, `TryExec` is checked by
+/// [`fetch_entries`] as the result changes without any desktop file changing
+fn is_visible(entry: &Group, desktops: &[String]) -> bool {
+    if entry.boolean("NoDisplay") || entry.boolean("Hidden") {
+        return false;
+    }
+    let only_show_in = entry.list("OnlyShowIn");
+    if !only_show_in.is_empty()
+        && !only_show_in
             .iter()
-            .zip(keyword_entries)
-            .flat_map(|(categories, keywords)| {
-                let mut entries = Vec::new();
-                let category_iter = categories.split(";");
-                let keyword_iter = keywords.split(";");
-                for (category, keyword) in category_iter.zip(keyword_iter) {
-                    entries.push(category.to_string());
-                    entries.push(keyword.to_string());
-                }
-                entries
+            .any(|desktop| desktops.contains(desktop))
+    {
+        return false;
+    }
+    !entry
+        .list("NotShowIn")
+        .iter()
+        .any(|desktop| desktops.contains(desktop))
+}
+
+/// Returns None for files which are invalid or hidden, these still shadow files with the same id
+/// in data dirs of lower precedence.
+fn read_single_entry(options: &ReadOptions, id: String, path: PathBuf) -> Option<EntryInfo> {
+    let data = fs::read_to_string(&path).ok()?;
+    let desktop_file = DesktopFile::parse(&data)?;
+    let entry = desktop_file.entry();
+
+    if !options.show_hidden && !is_visible(entry, &options.desktops) {
+        return None;
+    }
+
+    let name = entry.localized("Name", &options.locales)?;
+    let icon_key = entry.string("Icon");
+    let field_values = FieldValues {
+        name: &name,
+        icon: icon_key.as_deref(),
+        location: &path,
+    };
+    let exec = parse_exec(&entry.string("Exec")?, &field_values)?;
+    // groups which are not listed in Actions are ignored, as are actions without Exec which
+    // would have to be activated over D-Bus
+    let actions = entry
+        .list("Actions")
+        .iter()
+        .filter_map(|id| {
+            let action = desktop_file.group(&format!("Desktop Action {id}"))?;
+            Some(DesktopAction {
+                name: action.localized("Name", &options.locales)?,
+                exec: parse_exec(&action.string("Exec")?, &field_values)?,
+                icon_name: action.string("Icon"),
+                icon: None,
             })
-            .collect::<Vec<_>>();
-        match (name, exec) {
-            (None, None) => (),
-            (None, Some(_)) => (),
-            (Some(_), None) => (),
-            (Some(name), Some(exec)) => {
-                entries.insert(
-                    name.clone(),
-                    EntryInfo {
-                        name,
-                        icon,
-                        categories,
-                        exec,
-                    },
-                );
-            }
+        })
+        .collect();
+    let terminal = entry.boolean("Terminal");
+    let working_dir = entry
+        .string("Path")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from);
+    let categories = entry
+        .list("Categories")
+        .into_iter()
+        .chain(entry.localized_list("Keywords", &options.locales))
+        .collect::<Vec<_>>();
+    Some(EntryInfo {
+        id,
+        name,
+        icon_name: icon_key,
+        icon: None,
+        categories,
+        exec,
+        try_exec: entry.string("TryExec"),
+        terminal,
+        actions,
+        path,
+        working_dir,
+    })
+}
+
+/// Reads the desktop files directly in `dir`, files in subdirectories get ids like
+/// `kde-foo.desktop`
+fn read_entry_dir(options: &ReadOptions, dir: &Path, id_prefix: &str) -> EntryDir {
+    let mut entry_dir = EntryDir::default();
+    let Ok(files) = fs::read_dir(dir) else {
+        return entry_dir;
+    };
+    for file in files.flatten() {
+        let Ok(file_name) = file.file_name().into_string() else {
+            continue;
+        };
+        let path = file.path();
+        if path.is_dir() {
+            entry_dir.subdirs.push(file_name);
+        } else if file_name.ends_with(".desktop") {
+            let id = format!("{id_prefix}{file_name}");
+            let entry = read_single_entry(options, id.clone(), path);
+            entry_dir.entries.push((id, entry));
         }
     }
+    entry_dir
 }
 
-fn read_entry_of_dirs(
-    config: &Config,
-    iconmap: &HashMap<String, IconVariant>,
-    path: String,
-) -> HashMap<String, EntryInfo> {
-    let mut entries = HashMap::new();
-    if let Ok(files) = fs::read_dir(format!("{path}/applications")) {
-        for file in files.flatten() {
-            if file
-                .file_name()
-                .to_str()
-                .unwrap_or_default()
-                .ends_with(".desktop")
-            {
-                read_single_entry(config, iconmap, &mut entries, file);
-            }
+/// Reads the desktop files below `dir`, unchanged directories come from the cache.
+/// Ids which are already known come from a data dir of higher precedence and are skipped.
+fn read_entries_of_dir(
+    cache: &mut Cache,
+    options: &ReadOptions,
+    dir: &Path,
+    id_prefix: &str,
+    entries: &mut HashMap<String, Option<EntryInfo>>,
+) {
+    let entry_dir = cache.entry_dir(dir, |dir| read_entry_dir(options, dir, id_prefix));
+    for (id, entry) in entry_dir.entries {
+        if let hash_map::Entry::Vacant(vacant) = entries.entry(id) {
+            vacant.insert(entry);
         }
-    };
-    entries
+    }
+    for subdir in entry_dir.subdirs {
+        let prefix = format!("{id_prefix}{subdir}-");
+        read_entries_of_dir(cache, options, &dir.join(subdir), &prefix, entries);
+    }
 }
 
-pub fn fetch_entries(config: Config) -> (Message, Option<String>) {
-    let dir_iter = DATA_DIRS.into_iter().flat_map(|val| {
-        let dirs_res = env::var(val);
-        if let Ok(dirs) = dirs_res {
-            dirs.split(":").map(String::from).collect::<Vec<String>>()
+/// The data dirs in the order of their precedence.
+/// `XDG_DATA_HOME` and `XDG_DATA_DIRS` fall back to the defaults of the spec when unset, the
+/// flatpak and Nix dirs and the `extra_dirs` of the config follow.
+fn data_dirs(config: &Config) -> Vec<PathBuf> {
+    let base_dirs = xdg::BaseDirectories::new();
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let user = env::var("USER").unwrap_or_default();
+    let well_known_dirs = EXTRA_DATA_DIRS.into_iter().filter_map(|dir| {
+        let dir = dir.replace("$USER", &user);
+        if dir.starts_with('/') {
+            Some(PathBuf::from(dir))
         } else {
-            Vec::new()
+            home.as_ref().map(|home| home.join(dir))
         }
     });
 
-    let iconmap = dir_iter
-        .clone()
-        .flat_map(read_icons_per_dir)
-        .collect::<HashMap<String, IconVariant>>();
+    let mut dirs = Vec::new();
+    for dir in base_dirs
+        .get_data_home()
+        .into_iter()
+        .chain(base_dirs.get_data_dirs())
+        .chain(well_known_dirs)
+        .chain(config.extra_dirs.iter().cloned())
+    {
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// Reads the applications of all data dirs, applications hidden by the desktop file are only
+/// listed with `show_hidden`. The applications in `hidden` still shadow those with the same id in
+/// data dirs of lower precedence.
+pub fn fetch_entries(
+    config: &Config,
+    hidden: &[String],
+    cache: &mut Cache,
+) -> (Message, Option<String>) {
+    let dirs = data_dirs(config);
+    let options = ReadOptions {
+        locales: current_locales(),
+        desktops: current_desktops(),
+        show_hidden: config.show_hidden,
+    };
+    cache.begin(&options);
+
+    // later inserts win, which gives the icons of the first dirs precedence
+    let mut iconmap = HashMap::new();
+    for dir in dirs.iter().rev() {
+        iconmap.extend(read_icons_per_dir(cache, dir));
+    }
 
-    let entries = dir_iter
-        .flat_map(|val| read_entry_of_dirs(&config, &iconmap, val))
-        .collect::<HashMap<String, EntryInfo>>()
-        .into_values()
+    let mut entries_by_id = HashMap::new();
+    for dir in &dirs {
+        let dir = dir.join("applications");
+        read_entries_of_dir(cache, &options, &dir, "", &mut entries_by_id);
+    }
+    let mut entries = entries_by_id
+        .into_iter()
+        .filter(|(id, _)| !hidden.contains(id))
+        .filter_map(|(_, entry)| entry)
+        // checked after reading from the cache, installing a program does not change the data dirs
+        .filter(|entry| options.show_hidden || entry.try_exec.as_deref().is_none_or(is_executable))
         .collect::<Vec<_>>();
+    // the map has no order, applications ranked the same keep theirs across reads
+    entries.sort_by(|first, second| first.id.cmp(&second.id));
+    for entry in &mut entries {
+        entry.icon = entry
+            .icon_name
+            .as_ref()
+            .map(|icon| resolve_icon(&iconmap, icon));
+        for action in &mut entry.actions {
+            action.icon = action
+                .icon_name
+                .as_ref()
+                .map(|icon| resolve_icon(&iconmap, icon));
+        }
+    }
 
     if entries.is_empty() {
         (
@@ -278,44 +624,76 @@ pub fn fetch_entries(config: Config) -> (Message, Option<String>) {
     }
 }
 
-pub fn create_entry_card<'a>(entry: EntryInfo) -> Element<'a, Message> {
-    let icon = entry.icon.as_ref().and_then(|icon| match icon {
-        IconVariant::Svg(path_buf) => {
-            let handle = iced::widget::svg::Handle::from_path(path_buf);
-            let widget: Element<Message> = iced::widget::svg(handle)
-                .height(Length::Fixed(ICON_SIZE))
-                .width(Length::Fixed(ICON_SIZE))
-                .into();
-            Some(widget)
-        }
-        IconVariant::Png(path_buf) => Some(
-            iced::widget::image(path_buf)
-                .height(Length::Fixed(ICON_SIZE))
-                .width(Length::Fixed(ICON_SIZE))
-                .into(),
-        ),
-        IconVariant::Invalid => None,
+/// The directories whose changes affect the applications, which are the ones read by the last
+/// [`fetch_entries`], the data dirs for newly created subdirectories and the symlinks the data
+/// dirs resolve through
+fn watches(config: &Config, cache: &Cache) -> Vec<Watch> {
+    let mut watches = cache.visited().map(Watch::any).collect::<Vec<_>>();
+    for dir in data_dirs(config) {
+        watches.push(Watch::names(&dir, &["applications", "icons", "pixmaps"]));
+        watches.push(Watch::names(&dir.join("icons"), &ICON_THEMES));
+        watches.extend(link_watches(&dir));
+    }
+    watches
+}
+
+/// The applications shown while nothing is entered, see [`EmptyQuery`]
+pub fn empty_query_applications(
+    applications: &[EntryInfo],
+    empty_query: EmptyQuery,
+    history: &History,
+    pinned: &[String],
+) -> Vec<ScoredEntryInfo> {
+    let scored_entry = |entry: &EntryInfo, score: i64| ScoredEntryInfo {
+        id: entry_id_of(&entry.id, None),
+        score,
+        title: entry.name.clone(),
+        icon: entry.icon.clone(),
+    };
+    // in the order of the pinned list, the scores keep that order across plugins
+    let pinned_applications = pinned.iter().enumerate().filter_map(|(position, id)| {
+        let entry = applications.iter().find(|entry| entry.id == *id)?;
+        let score = PINNED_BOOST + (pinned.len() - position) as i64;
+        Some(scored_entry(entry, score))
     });
-    let content = Row::new().push(icon).push(
-        container(
-            text(entry.name.clone())
-                .align_y(Alignment::Center)
-                .height(Length::Fill),
-        )
-        .align_right(Length::Fill),
-    );
-    content.into()
+    match empty_query {
+        EmptyQuery::Recent => {
+            let mut recent_applications = applications
+                .iter()
+                .filter(|entry| history.frecency(&entry.id) > 0 && !pinned.contains(&entry.id))
+                .collect::<Vec<_>>();
+            recent_applications.sort_by_key(|entry| Reverse(history.frecency(&entry.id)));
+            let recent_applications = recent_applications
+                .into_iter()
+                .map(|entry| scored_entry(entry, history.boost(&entry.id)));
+            pinned_applications.chain(recent_applications).collect()
+        }
+        EmptyQuery::Pinned => pinned_applications.collect(),
+        EmptyQuery::Alphabetical => {
+            let mut alphabetical_applications = applications
+                .iter()
+                .map(|entry| scored_entry(entry, 0))
+                .collect::<Vec<_>>();
+            alphabetical_applications
+                .sort_by_cached_key(|scored_entry| scored_entry.title.to_lowercase());
+            alphabetical_applications
+        }
+        EmptyQuery::Nothing => Vec::new(),
+    }
 }
 
+/// Ranks the applications and their desktop actions by how well they match the filter text,
+/// boosted by how often and how recently they were launched, pinned matches rank first.
 pub fn sort_appliations(
     applications: Vec<EntryInfo>,
     filter_text: String,
     fuzzy_matcher: Arc<SkimMatcherV2>,
-) -> Message {
-    let mut sorted_applications = applications
-        .clone()
-        .into_iter()
-        .filter_map(|entry| {
+    history: &History,
+    pinned: &[String],
+) -> Vec<ScoredEntryInfo> {
+    let scored_applications = applications
+        .iter()
+        .flat_map(|entry| {
             let mut category_scores = Vec::new();
             let name_score = fuzzy_matcher.fuzzy_match(&entry.name, &filter_text);
 
@@ -329,203 +707,293 @@ pub fn sort_appliations(
 
             let name_max = name_score.unwrap_or(0);
             let score = *category_scores.iter().max().unwrap_or(&0).max(&name_max);
-            if score < SORT_THRESHOLD {
-                None
-            } else {
-                Some(ScoredEntryInfo { score, entry })
+            let application = ScoredEntryInfo {
+                id: entry_id_of(&entry.id, None),
+                score,
+                title: entry.name.clone(),
+                icon: entry.icon.clone(),
+            };
+            // desktop actions are matched by their full title, e.g. "Firefox: New Window"
+            let actions = entry
+                .actions
+                .iter()
+                .enumerate()
+                .map(|(action_index, action)| {
+                    let title = format!("{}: {}", entry.name, action.name);
+                    ScoredEntryInfo {
+                        id: entry_id_of(&entry.id, Some(action_index)),
+                        score: fuzzy_matcher.fuzzy_match(&title, &filter_text).unwrap_or(0),
+                        title,
+                        icon: action.icon.clone().or_else(|| entry.icon.clone()),
+                    }
+                })
+                .collect::<Vec<_>>();
+            let is_pinned = pinned.contains(&entry.id);
+            let boost = history.boost(&entry.id);
+            // the boost only reorders matches, it never turns a non-match into one
+            std::iter::once(application)
+                .chain(actions)
+                .filter(|scored_entry| scored_entry.score >= SORT_THRESHOLD)
+                .map(move |mut scored_entry| {
+                    scored_entry.score += boost;
+                    (is_pinned, scored_entry)
+                })
+        })
+        .collect::<Vec<_>>();
+    // pinned matches form a tier of their own, lifted above the best unpinned match so the
+    // order also holds once OxiRun ranks them among the results of other plugins
+    let best_unpinned = scored_applications
+        .iter()
+        .filter(|(is_pinned, _)| !is_pinned)
+        .map(|(_, scored_entry)| scored_entry.score)
+        .max()
+        .unwrap_or(0);
+    let mut sorted_applications = scored_applications
+        .into_iter()
+        .map(|(is_pinned, mut scored_entry)| {
+            if is_pinned {
+                scored_entry.score += best_unpinned;
             }
+            scored_entry
         })
         .collect::<Vec<_>>();
-    sorted_applications.sort_by(|first, second| second.score.cmp(&first.score));
-    Message::ReceiveSortedEntries(sorted_applications)
-}
-
-pub fn to_oxiany_rc(msg: Message) -> Arc<dyn OxiAny> {
-    let boxed = Box::leak(Box::new(msg));
-    Arc::new(boxed as &dyn OxiAny)
+    sorted_applications.sort_by(|first, second| {
+        second
+            .score
+            .cmp(&first.score)
+            .then_with(|| first.title.cmp(&second.title))
+    });
+    sorted_applications
 }
 
-pub async fn to_oxiany_async(msg: Message) -> Arc<dyn OxiAny> {
-    let boxed = Box::leak(Box::new(msg));
-    Arc::new(boxed as &dyn OxiAny)
-}
+impl Plugin for Model {
+    type Message = Message;
+    const NAME: &'static str = "Applications";
+
+    fn new(global_config: Table, context: Context<Message>) -> Self {
+        let mut model = Model::default();
+        let (config, config_error_opt) = get_config(global_config);
+        model.config = config;
+        model.errors.extend(config_error_opt);
+        let (history, history_error_opt) = History::load();
+        model.history = history;
+        model.errors.extend(history_error_opt);
+        let (user_lists, user_lists_error_opt) = UserLists::load();
+        model.user_lists = user_lists;
+        model.errors.extend(user_lists_error_opt);
+
+        // a daemon keeps the plugin loaded, changed data dirs are read again until it is dropped
+        let mut watcher = None;
+        if context.daemon() {
+            match Watcher::new() {
+                Ok(new_watcher) => {
+                    model.stop_watching = Some(new_watcher.stop_handle());
+                    watcher = Some(new_watcher);
+                }
+                Err(error) => model.errors.push(error),
+            }
+        }
 
-pub fn run_command(command: &str) {
-    let res = Command::new("sh")
-        .arg("-c")
-        .arg(format!("nohup {command} >/dev/null 2>&1 &"))
-        .spawn();
+        let config = model.config.clone();
+        let hidden = model.hidden();
+        model.fetch_handle = Some(std::thread::spawn(move || {
+            let mut cache = Cache::load();
+            loop {
+                let (entries, entry_error_opt) = fetch_entries(&config, &hidden, &mut cache);
+                if let Some(value) = entry_error_opt {
+                    context.send(Message::ReceiveError(value));
+                }
+                context.send(entries);
+                if let Err(error) = cache.save() {
+                    context.send(Message::ReceiveError(error));
+                }
+                let Some(watcher) = &mut watcher else {
+                    break;
+                };
+                if !watcher.wait(watches(&config, &cache)) {
+                    break;
+                }
+            }
+        }));
 
-    if let Err(error) = res {
-        panic!("Failed to spawn command: {error}");
+        model
     }
-}
 
-#[unsafe(no_mangle)]
-#[allow(improper_ctypes_definitions)]
-pub extern "C" fn model(
-    global_config: Table,
-) -> (
-    Arc<RwLock<&'static mut dyn OxiAny>>,
-    Option<Task<Arc<dyn OxiAny>>>,
-) {
-    let model = Box::leak(Box::new(Model::new(global_config)));
+    fn update(&mut self, msg: Message) {
+        match msg {
+            Message::ReceiveEntries(entry_infos) => {
+                // hidden from the launcher after the entries were read
+                let hidden = self.hidden();
+                self.applications = entry_infos
+                    .into_iter()
+                    .filter(|entry| !hidden.contains(&entry.id))
+                    .collect();
+                self.sort();
+            }
+            Message::ReceiveError(error) => self.errors.push(error),
+        }
+    }
 
-    let config = model.config.clone();
+    fn query(&mut self, filter_text: &str) {
+        self.filter_text = Some(filter_text.to_string());
+        self.sort();
+    }
 
-    let (entries, entry_error_opt) = fetch_entries(config);
-    if let Some(value) = entry_error_opt {
-        model.errors.push(value)
+    fn no_query(&mut self) {
+        self.filter_text = None;
+        self.sort();
     }
 
-    // TODO get config from main app perhaps? if so how? this should only take subkeys
-    (
-        Arc::new(RwLock::new(model as &'static mut dyn OxiAny)),
-        Some(Task::future(to_oxiany_async(entries))),
-    )
-}
+    fn launch(&mut self, entry_id: u64, activation_token: Option<&str>) {
+        let Some((entry, exec)) = self
+            .command(entry_id)
+            .map(|(entry, exec)| (entry.clone(), exec.to_vec()))
+        else {
+            self.errors.push("Could not get entry for id".into());
+            return;
+        };
+        self.run_exec(&entry, &exec, entry.terminal, activation_token);
+    }
 
-#[unsafe(no_mangle)]
-#[allow(improper_ctypes_definitions)]
-pub extern "C" fn update(
-    filter_text: String,
-    model: Arc<RwLock<&'static mut dyn OxiAny>>,
-    msg: Arc<&'static mut dyn OxiAny>,
-) -> Option<Task<Arc<dyn OxiAny>>> {
-    let mut model_borrow = model.try_write().ok()?;
-    let model = model_borrow.downcast_mut::<Model>()?;
-    let msg_opt = msg.downcast_ref::<Message>();
-    if msg_opt.is_none() {
-        model
-            .errors
-            .push(String::from("Can't get message in update"));
-        return None;
-    };
-    let msg = msg_opt.unwrap().to_owned();
-    match msg {
-        Message::ReceiveEntries(entry_infos) => {
-            model.applications = entry_infos.clone();
-            let matcher = model.fuzzy_matcher.clone();
-            Some(Task::future(to_oxiany_async(sort_appliations(
-                entry_infos.clone(),
-                filter_text,
-                matcher,
-            ))))
-        }
-        Message::ReceiveSortedEntries(scored_entry_infos) => {
-            model.sorted_applications = scored_entry_infos;
-            None
-        }
+    fn entries(&self) -> Vec<Entry<'_>> {
+        self.sorted_applications
+            .iter()
+            .take(self.config.max_entries)
+            .map(|scored_entry| Entry {
+                id: scored_entry.id,
+                score: scored_entry.score,
+                title: &scored_entry.title,
+                subtitle: None,
+                icon: scored_entry
+                    .icon
+                    .as_ref()
+                    .and_then(IconVariant::path)
+                    .map(PathBuf::as_path),
+            })
+            .collect()
     }
-}
 
-#[unsafe(no_mangle)]
-#[allow(improper_ctypes_definitions)]
-pub extern "C" fn sort(
-    filter_text: String,
-    model: Arc<RwLock<&'static mut dyn OxiAny>>,
-) -> Option<Task<Arc<dyn OxiAny>>> {
-    let mut model_borrow = model.try_write().ok()?;
-    let model = model_borrow.downcast_mut::<Model>()?;
-    let applications = model.applications.clone();
-    Some(Task::future(to_oxiany_async(sort_appliations(
-        applications,
-        filter_text,
-        model.fuzzy_matcher.clone(),
-    ))))
-}
+    fn errors(&self) -> &[String] {
+        &self.errors
+    }
 
-#[unsafe(no_mangle)]
-#[allow(improper_ctypes_definitions)]
-pub extern "C" fn launch(
-    focused_index: usize,
-    model: Arc<RwLock<&'static mut dyn OxiAny>>,
-) -> Option<Task<&'static dyn OxiAny>> {
-    let lock = model.try_write();
-    if let Ok(mut model_borrow) = lock {
-        let model_opt = model_borrow.downcast_mut::<Model>();
-        if let Some(model) = model_opt {
-            let exec_opt = &model.sorted_applications.get(focused_index);
-            if exec_opt.is_none() {
-                model.errors.push("Could not get entry for index".into());
-                return None;
-            }
-            let exec = &exec_opt.unwrap().entry.exec;
-            run_command(exec);
+    fn reconfigure(&mut self, global_config: Table) -> bool {
+        let (config, config_error_opt) = get_config(global_config);
+        // the entries have to be read again
+        if config.show_hidden != self.config.show_hidden
+            || config.extra_dirs != self.config.extra_dirs
+            || config.hidden != self.config.hidden
+        {
+            return false;
         }
+        self.config = config;
+        // errors reported before belong to the previous config
+        self.errors.clear();
+        self.errors.extend(config_error_opt);
+        // the empty query depends on the config
+        self.sort();
+        true
     }
-    None
-}
 
-#[unsafe(no_mangle)]
-#[allow(improper_ctypes_definitions)]
-pub extern "C" fn view(
-    model: Arc<RwLock<&'static mut dyn OxiAny>>,
-) -> Result<Vec<(i64, Element<'static, Arc<dyn OxiAny>>)>, std::io::Error> {
-    let lock = model.try_read();
-    if let Ok(model_borrow) = lock {
-        let model = model_borrow
-            .downcast_ref::<Model>()
-            .ok_or(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "Could not get model in view",
-            ))?;
-        let entries: Vec<(i64, Element<Arc<dyn OxiAny>>)> = model
-            .sorted_applications
-            .clone()
-            .into_iter()
-            .take(model.config.max_entries)
-            .map(|scored_entry| {
-                (
-                    scored_entry.score,
-                    Into::<Element<Message>>::into(create_entry_card(scored_entry.entry))
-                        .map(to_oxiany_rc),
-                )
-            })
-            .collect::<Vec<_>>();
-        Ok(entries)
-    } else {
-        Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            "Could not get model in view",
-        ))
+    fn actions(&self) -> Vec<&str> {
+        vec!["pin", "hide"]
     }
-}
 
-#[unsafe(no_mangle)]
-#[allow(improper_ctypes_definitions)]
-pub extern "C" fn name() -> &'static str {
-    "Applications"
-}
-
-#[unsafe(no_mangle)]
-#[allow(improper_ctypes_definitions)]
-pub extern "C" fn errors(model: Arc<RwLock<&'static mut dyn OxiAny>>) -> Vec<String> {
-    let lock = model.try_read();
-    if let Ok(model_borrow) = lock {
-        let model_opt = model_borrow.downcast_ref::<Model>();
-        if let Some(model) = model_opt {
-            model.errors.clone()
-        } else {
-            vec![String::from("Could not get model while fetching errors")]
+    fn run_action(&mut self, action: &str, entry_id: u64) {
+        match action {
+            "pin" => self.toggle_pin(entry_id),
+            "hide" => self.hide(entry_id),
+            _ => (),
         }
-    } else {
-        vec![String::from("Could not get model while fetching errors")]
     }
-}
 
-#[unsafe(no_mangle)]
-#[allow(improper_ctypes_definitions)]
-pub extern "C" fn count(model: Arc<RwLock<&'static mut dyn OxiAny>>) -> usize {
-    let lock = model.try_read();
-    if let Ok(model_borrow) = lock {
-        let model_opt = model_borrow.downcast_ref::<Model>();
-        if let Some(model) = model_opt {
-            usize::min(model.sorted_applications.len(), model.config.max_entries)
+    fn entry_actions(&self, entry_id: u64) -> Vec<EntryAction<'_>> {
+        let (_, action_index) = split_entry_id(entry_id);
+        let Some(entry) = self.application(entry_id) else {
+            return Vec::new();
+        };
+        // desktop actions are only offered for the application itself
+        let desktop_actions = entry
+            .actions
+            .iter()
+            .zip(DESKTOP_ACTION_OFFSET..)
+            .filter(|_| action_index.is_none())
+            .map(|(action, id)| {
+                let icon = action.icon.as_ref().and_then(IconVariant::path);
+                (id, action.name.as_str(), icon.map(PathBuf::as_path))
+            });
+        // applications pinned in the config can only be unpinned there
+        let pin_title = if self.config.pinned.contains(&entry.id) {
+            None
+        } else if self.user_lists.pinned().contains(&entry.id) {
+            Some("Unpin")
         } else {
-            0
+            Some("Pin")
+        };
+        let actions = [
+            (RUN_ACTION, Some("Run")),
+            (RUN_IN_TERMINAL_ACTION, Some("Run in terminal")),
+            (COPY_COMMAND_ACTION, Some("Copy command")),
+            (OPEN_FOLDER_ACTION, Some("Open containing folder")),
+            (PIN_ACTION, pin_title),
+            (HIDE_ACTION, Some("Hide")),
+        ]
+        .into_iter()
+        .filter_map(|(id, title)| Some((id, title?, None)));
+        desktop_actions
+            .chain(actions)
+            .map(|(id, title, icon)| EntryAction { id, title, icon })
+            .collect()
+    }
+
+    fn launch_action(
+        &mut self,
+        entry_id: u64,
+        action_id: u64,
+        activation_token: Option<&str>,
+    ) -> bool {
+        let Some((entry, exec)) = self
+            .command(entry_id)
+            .map(|(entry, exec)| (entry.clone(), exec.to_vec()))
+        else {
+            self.errors.push("Could not get entry for id".into());
+            return false;
+        };
+        match action_id {
+            RUN_ACTION => self.run_exec(&entry, &exec, entry.terminal, activation_token),
+            RUN_IN_TERMINAL_ACTION => self.run_exec(&entry, &exec, true, activation_token),
+            COPY_COMMAND_ACTION => self.spawn("wl-copy", join_args(&exec), None),
+            OPEN_FOLDER_ACTION => {
+                let folder = entry.path.parent().unwrap_or(&entry.path);
+                let folder = folder.to_string_lossy().to_string();
+                self.spawn("xdg-open", folder, activation_token);
+            }
+            // these change the results, OxiRun stays open to show them
+            PIN_ACTION => {
+                self.toggle_pin(entry_id);
+                return false;
+            }
+            HIDE_ACTION => {
+                self.hide(entry_id);
+                return false;
+            }
+            _ => {
+                let action = action_id
+                    .checked_sub(DESKTOP_ACTION_OFFSET)
+                    .and_then(|index| entry.actions.get(index as usize));
+                match action {
+                    Some(action) => {
+                        self.run_exec(&entry, &action.exec, entry.terminal, activation_token)
+                    }
+                    None => {
+                        self.errors.push("Could not get action for id".into());
+                        return false;
+                    }
+                }
+            }
         }
-    } else {
-        0
+        true
     }
 }
+
+export_plugin!(Model);
@@ -0,0 +1,65 @@
+//! Files the plugin keeps across runs of OxiRun, in the XDG state and cache dirs.
+
+use std::{fmt::Display, fs, io::ErrorKind, path::PathBuf};
+
+#[derive(Debug, Default)]
+pub struct StateFile {
+    path: Option<PathBuf>,
+    /// Named in the errors when the base directory is unknown
+    base_dir: &'static str,
+}
+
+impl StateFile {
+    /// `$XDG_STATE_HOME/oxirun/<name>`
+    pub fn state(name: &str) -> Self {
+        Self {
+            path: xdg::BaseDirectories::new()
+                .get_state_home()
+                .map(|state_home| state_home.join("oxirun").join(name)),
+            base_dir: "state",
+        }
+    }
+
+    /// `$XDG_CACHE_HOME/oxirun/<name>`
+    pub fn cache(name: &str) -> Self {
+        Self {
+            path: xdg::BaseDirectories::new()
+                .get_cache_home()
+                .map(|cache_home| cache_home.join("oxirun").join(name)),
+            base_dir: "cache",
+        }
+    }
+
+    /// Returns the content of the file, None if the file or its base directory does not exist
+    pub fn read(&self) -> Result<Option<String>, String> {
+        let Some(path) = &self.path else {
+            return Ok(None);
+        };
+        match fs::read_to_string(path) {
+            Ok(data) => Ok(Some(data)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(format!("Could not read {}: {error}", path.display())),
+        }
+    }
+
+    /// Replaces the content of the file, creating the directories leading to it
+    pub fn write(&self, data: &str) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Err(format!("Could not find the {} directory", self.base_dir));
+        };
+        path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(path, data))
+            .map_err(|error| format!("Could not write {}: {error}", path.display()))
+    }
+}
+
+/// The path of the file, used in errors about its content
+impl Display for StateFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}", path.display()),
+            None => write!(f, "the {} file", self.base_dir),
+        }
+    }
+}
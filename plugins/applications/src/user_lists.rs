@@ -0,0 +1,89 @@
+//! Applications pinned and hidden from the launcher with the pin and hide actions.
+//!
+//! Stored in `$XDG_STATE_HOME/oxirun/applications.toml` with the same keys as the config, the
+//! lists of both are merged.
+
+use serde::{Deserialize, Serialize};
+
+use crate::state_file::StateFile;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Lists {
+    #[serde(default)]
+    pinned: Vec<String>,
+    #[serde(default)]
+    hidden: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct UserLists {
+    file: StateFile,
+    lists: Lists,
+}
+
+impl UserLists {
+    /// Reads the state file, a missing file means both lists are empty
+    pub fn load() -> (Self, Option<String>) {
+        let mut user_lists = Self {
+            file: StateFile::state("applications.toml"),
+            lists: Lists::default(),
+        };
+        let data = match user_lists.file.read() {
+            Ok(Some(data)) => data,
+            Ok(None) => return (user_lists, None),
+            Err(error) => return (user_lists, Some(error)),
+        };
+        match toml::from_str(&data) {
+            Ok(lists) => {
+                user_lists.lists = lists;
+                (user_lists, None)
+            }
+            Err(error) => {
+                let error = format!(
+                    "Invalid {}: {}",
+                    user_lists.file,
+                    error.to_string().trim_end()
+                );
+                (user_lists, Some(error))
+            }
+        }
+    }
+
+    pub fn pinned(&self) -> &[String] {
+        &self.lists.pinned
+    }
+
+    pub fn hidden(&self) -> &[String] {
+        &self.lists.hidden
+    }
+
+    /// Appends the application to the pinned list and writes the state file
+    pub fn pin(&mut self, id: &str) -> Result<(), String> {
+        if !self.lists.pinned.iter().any(|pinned| pinned == id) {
+            self.lists.pinned.push(id.to_string());
+        }
+        self.save()
+    }
+
+    /// Removes the application from the pinned list and writes the state file
+    pub fn unpin(&mut self, id: &str) -> Result<(), String> {
+        self.lists.pinned.retain(|pinned| pinned != id);
+        self.save()
+    }
+
+    /// Adds the application to the hidden list and writes the state file, hidden applications
+    /// are no longer pinned
+    pub fn hide(&mut self, id: &str) -> Result<(), String> {
+        self.lists.pinned.retain(|pinned| pinned != id);
+        if !self.lists.hidden.iter().any(|hidden| hidden == id) {
+            self.lists.hidden.push(id.to_string());
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let data = toml::to_string(&self.lists)
+            .map_err(|error| format!("Could not serialize {}: {error}", self.file))?;
+        self.file.write(&data)
+    }
+}
@@ -0,0 +1,242 @@
+//! Watches the data dirs with inotify while OxiRun runs as daemon, so installed and removed
+//! applications show up without restarting it.
+//!
+//! Data dirs which do not exist yet are not watched, they are picked up with the next change of
+//! a watched one.
+
+use std::{
+    collections::{HashMap, hash_map},
+    ffi::{OsStr, OsString},
+    fs,
+    io::{PipeReader, PipeWriter, Write},
+    os::fd::{AsFd, AsRawFd},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use inotify::{Event, EventMask, Inotify, WatchDescriptor, WatchMask};
+
+const WATCH_MASK: WatchMask = WatchMask::CREATE
+    .union(WatchMask::DELETE)
+    .union(WatchMask::MOVED_FROM)
+    .union(WatchMask::MOVED_TO)
+    .union(WatchMask::DELETE_SELF)
+    .union(WatchMask::MOVE_SELF)
+    .union(WatchMask::ONLYDIR);
+/// Package managers change many files at once, the dirs are read again once this passed after
+/// the first change
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+/// Bounds the symlinks followed by [`link_watches`], like the `ELOOP` limit of the kernel
+const MAX_LINKS: usize = 40;
+
+/// A directory to watch, a change of any file in it counts unless `names` restricts them
+#[derive(Debug, Clone)]
+pub struct Watch {
+    pub dir: PathBuf,
+    pub names: Option<Vec<OsString>>,
+}
+
+impl Watch {
+    pub fn any(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            names: None,
+        }
+    }
+
+    pub fn names(dir: &Path, names: &[&str]) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            names: Some(names.iter().map(OsString::from).collect()),
+        }
+    }
+}
+
+/// Watches for the directories containing the symlinks `path` resolves through. Nix switches
+/// profiles by replacing such a symlink, the directories behind it never change.
+pub fn link_watches(path: &Path) -> Vec<Watch> {
+    let mut watches = Vec::new();
+    let mut remaining = path
+        .components()
+        .map(|component| component.as_os_str().to_os_string())
+        .collect::<Vec<_>>();
+    remaining.reverse();
+    let mut resolved = PathBuf::new();
+    while let Some(component) = remaining.pop() {
+        let next = resolved.join(&component);
+        match fs::read_link(&next) {
+            Ok(target) if watches.len() < MAX_LINKS => {
+                watches.push(Watch {
+                    dir: resolved.clone(),
+                    names: Some(vec![component]),
+                });
+                // an absolute target replaces the resolved path when it is joined
+                remaining.extend(
+                    target
+                        .components()
+                        .rev()
+                        .map(|component| component.as_os_str().to_os_string()),
+                );
+            }
+            _ => resolved = next,
+        }
+    }
+    watches
+}
+
+/// Stops the thread blocked in [`Watcher::wait`], can be used from any thread
+#[derive(Debug, Clone)]
+pub struct StopHandle(Arc<PipeWriter>);
+
+impl StopHandle {
+    pub fn stop(&self) {
+        let _ = (&*self.0).write_all(&[1]);
+    }
+}
+
+enum Wakeup {
+    Stopped,
+    Changed,
+    Timeout,
+}
+
+pub struct Watcher {
+    inotify: Inotify,
+    /// Readable once [`StopHandle::stop`] was called
+    stop: PipeReader,
+    stop_writer: Arc<PipeWriter>,
+    /// The file names each watch is restricted to, None for any file
+    watches: HashMap<WatchDescriptor, Option<Vec<OsString>>>,
+}
+
+impl Watcher {
+    pub fn new() -> Result<Self, String> {
+        let inotify = Inotify::init()
+            .map_err(|error| format!("Could not watch the applications: {error}"))?;
+        let (stop, stop_writer) = std::io::pipe()
+            .map_err(|error| format!("Could not watch the applications: {error}"))?;
+        Ok(Self {
+            inotify,
+            stop,
+            stop_writer: Arc::new(stop_writer),
+            watches: HashMap::new(),
+        })
+    }
+
+    pub fn stop_handle(&self) -> StopHandle {
+        StopHandle(self.stop_writer.clone())
+    }
+
+    /// Replaces the watched directories with `watches` and blocks until one of them changed and
+    /// settled. Returns false once stopped.
+    pub fn wait(&mut self, watches: Vec<Watch>) -> bool {
+        self.rewatch(watches);
+        loop {
+            match self.poll(None) {
+                Wakeup::Stopped => return false,
+                Wakeup::Changed if self.read_events() => break,
+                Wakeup::Changed | Wakeup::Timeout => (),
+            }
+        }
+        // the events until then are drained, they belong to the same change
+        let deadline = Instant::now() + SETTLE_TIME;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.poll(Some(timeout)) {
+                Wakeup::Stopped => return false,
+                Wakeup::Changed => {
+                    self.read_events();
+                }
+                Wakeup::Timeout => return true,
+            }
+        }
+    }
+
+    fn rewatch(&mut self, watches: Vec<Watch>) {
+        for (wd, _) in self.watches.drain() {
+            let _ = self.inotify.watches().remove(wd);
+        }
+        for watch in watches {
+            let Ok(wd) = self.inotify.watches().add(&watch.dir, WATCH_MASK) else {
+                continue;
+            };
+            // the same directory can be watched for any file and for a symlink in it
+            match self.watches.entry(wd) {
+                hash_map::Entry::Vacant(vacant) => {
+                    vacant.insert(watch.names);
+                }
+                hash_map::Entry::Occupied(mut occupied) => {
+                    match (occupied.get_mut(), watch.names) {
+                        (Some(names), Some(added)) => names.extend(added),
+                        (names, _) => *names = None,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Blocks until the watcher was stopped, an event is pending or the timeout passed
+    fn poll(&self, timeout: Option<Duration>) -> Wakeup {
+        let mut fds = [
+            libc::pollfd {
+                fd: self.stop.as_fd().as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: self.inotify.as_fd().as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        // rounded up, the settle time would otherwise end with polls that return right away
+        let timeout = timeout.map_or(-1, |timeout| {
+            timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32
+        });
+        // the descriptors are owned by the watcher and outlive the call
+        let result = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+        if result == -1 {
+            // interrupted by a signal, which simply counts as a wakeup without any events
+            return match std::io::Error::last_os_error().kind() {
+                std::io::ErrorKind::Interrupted => Wakeup::Changed,
+                _ => Wakeup::Stopped,
+            };
+        }
+        if fds[0].revents != 0 {
+            Wakeup::Stopped
+        } else if fds[1].revents != 0 {
+            Wakeup::Changed
+        } else {
+            Wakeup::Timeout
+        }
+    }
+
+    /// Reads all pending events without blocking, returns whether one of them is about a watched
+    /// file
+    fn read_events(&mut self) -> bool {
+        let mut buffer = [0; 4096];
+        let mut relevant = false;
+        // fails with WouldBlock once no events are left
+        while let Ok(events) = self.inotify.read_events(&mut buffer) {
+            for event in events {
+                relevant |= self.is_relevant(&event);
+            }
+        }
+        relevant
+    }
+
+    fn is_relevant(&self, event: &Event<&OsStr>) -> bool {
+        // the queue overflowed, any directory might have changed
+        if event.mask.contains(EventMask::Q_OVERFLOW) {
+            return true;
+        }
+        match self.watches.get(&event.wd) {
+            Some(None) => true,
+            Some(Some(names)) => event
+                .name
+                .is_some_and(|name| names.iter().any(|watched| watched == name)),
+            None => false,
+        }
+    }
+}